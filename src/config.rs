@@ -0,0 +1,373 @@
+use crate::manifests::ManifestType;
+use crate::model::SectionKind;
+use crate::remote::Host;
+use crate::scheme::{CalVerFormat, Scheme, SchemeKind};
+use serde::Deserialize;
+use std::fs::read_to_string;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use toml_edit::Document;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    pub changelog: PathBuf,
+    /// Directory of changelog fragments that are merged into the changelog on release.
+    pub fragments: PathBuf,
+    pub section_order: Vec<SectionKind>,
+    pub preid: Option<String>,
+    /// Prefix of release tags, `v` if not set.
+    pub tag_prefix: Option<String>,
+    pub scheme: SchemeKind,
+    /// The format of CalVer versions like `YYYY.0M.MICRO`.
+    pub calver_format: CalVerFormat,
+    pub compare_url: Option<String>,
+    /// The hosting service of the repository, detected from the `origin` remote if not set.
+    pub host: Option<Host>,
+    /// Template of release commit messages using `{version}` and `{tag}`.
+    pub commit_message: String,
+    /// Whether build metadata is included in the changelog headings and references.
+    pub build_metadata_in_changelog: bool,
+    pub manifests: Option<Vec<ManifestType>>,
+    pub python: PythonConfig,
+    pub collect: CollectConfig,
+    pub version_files: Vec<VersionFile>,
+    /// Releases the packages of a monorepo independently, if set.
+    pub monorepo: Option<MonorepoConfig>,
+}
+
+/// A file containing the version, like a README with install instructions.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct VersionFile {
+    /// A glob matching the files to update.
+    pub path: String,
+    /// The text surrounding the version, with a `{version}` placeholder that matches the
+    /// previous version.
+    pub search: String,
+}
+
+impl FromStr for VersionFile {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((path, search)) => Ok(Self {
+                path: path.into(),
+                search: search.into(),
+            }),
+            None => Err("expected `<path>=<search>`".into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct PythonConfig {
+    /// A module containing a `__version__ = "..."` assignment to update.
+    pub version_file: Option<PathBuf>,
+}
+
+/// Filters for the commits collected into the changelog.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct CollectConfig {
+    /// Only collect commits with one of these scopes, if not empty.
+    pub scopes: Vec<String>,
+    /// Skip commits with one of these scopes.
+    pub exclude_scopes: Vec<String>,
+}
+
+/// Settings of a repository with independently versioned packages.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct MonorepoConfig {
+    /// Globs matching the package directories, defaults to the Cargo or npm workspace members.
+    pub packages: Vec<String>,
+    /// Prefix of package release tags, using a `{package}` placeholder for the name.
+    pub tag_prefix: String,
+}
+
+impl Default for MonorepoConfig {
+    fn default() -> Self {
+        Self {
+            packages: Vec::new(),
+            tag_prefix: "{package}@v".into(),
+        }
+    }
+}
+
+impl CollectConfig {
+    pub fn includes(&self, scope: Option<&str>) -> bool {
+        let matches = |scopes: &[String]| scope.is_some_and(|s| scopes.iter().any(|x| x == s));
+        (self.scopes.is_empty() || matches(&self.scopes)) && !matches(&self.exclude_scopes)
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            changelog: PathBuf::from("CHANGELOG.md"),
+            fragments: PathBuf::from(".changes"),
+            section_order: SectionKind::ALL.to_vec(),
+            preid: None,
+            tag_prefix: None,
+            scheme: SchemeKind::default(),
+            calver_format: CalVerFormat::default(),
+            compare_url: None,
+            host: None,
+            commit_message: "Release {version}".into(),
+            build_metadata_in_changelog: false,
+            manifests: None,
+            python: PythonConfig::default(),
+            collect: CollectConfig::default(),
+            version_files: Vec::new(),
+            monorepo: None,
+        }
+    }
+}
+
+impl Config {
+    /// Loads the configuration from `protokollant.toml` or, if that does not exist, from
+    /// the `[package.metadata.protokollant]` table of `Cargo.toml` in the given directory.
+    pub fn discover(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref();
+
+        let config_path = dir.join("protokollant.toml");
+        if config_path.exists() {
+            let content = read_to_string(&config_path)?;
+            return Self::from_toml(&content).map_err(|e| invalid_config(&config_path, e));
+        }
+
+        let cargo_path = dir.join("Cargo.toml");
+        if cargo_path.exists() {
+            let content = read_to_string(&cargo_path)?;
+            return Self::from_cargo_manifest(&content).map_err(|e| invalid_config(&cargo_path, e));
+        }
+
+        Ok(Self::default())
+    }
+
+    /// The versioning scheme used to bump and format versions.
+    pub fn scheme(&self) -> Scheme {
+        match self.scheme {
+            SchemeKind::Semver => Scheme::SemVer,
+            SchemeKind::Calver => Scheme::CalVer(self.calver_format.clone()),
+        }
+    }
+
+    /// The prefix of release tags.
+    pub fn tag_prefix(&self) -> &str {
+        self.tag_prefix.as_deref().unwrap_or("v")
+    }
+
+    /// Checks that no top-level tag prefix is set in a monorepo, where the packages use
+    /// `monorepo.tag-prefix` instead.
+    pub fn check_tag_prefix(&self, monorepo: bool) -> std::result::Result<(), String> {
+        if (monorepo || self.monorepo.is_some()) && self.tag_prefix.is_some() {
+            return Err(
+                "`tag-prefix` does not apply to the packages of a monorepo, set `monorepo.tag-prefix` instead"
+                    .into(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Finds the project root, the nearest directory from `dir` upwards which contains a
+    /// `protokollant.toml` or a `CHANGELOG.md`. Defaults to `dir` itself.
+    pub fn find_root(dir: &Path) -> PathBuf {
+        dir.ancestors()
+            .find(|d| d.join("protokollant.toml").exists() || d.join("CHANGELOG.md").exists())
+            .unwrap_or(dir)
+            .to_path_buf()
+    }
+
+    /// The configuration of a package in the given directory of a monorepo, with its own
+    /// changelog, fragments and tags.
+    pub fn for_package(&self, name: &str, dir: &Path) -> Self {
+        let monorepo = self.monorepo.clone().unwrap_or_default();
+        Self {
+            changelog: dir.join(&self.changelog),
+            fragments: dir.join(&self.fragments),
+            tag_prefix: Some(monorepo.tag_prefix.replace("{package}", name)),
+            ..self.clone()
+        }
+    }
+
+    pub fn from_toml(content: &str) -> std::result::Result<Self, toml_edit::de::Error> {
+        let config = toml_edit::de::from_str::<Self>(content)?;
+        config.validate()
+    }
+
+    fn validate(self) -> std::result::Result<Self, toml_edit::de::Error> {
+        if let Some(preid) = &self.preid {
+            parse_preid(preid).map_err(<toml_edit::de::Error as serde::de::Error>::custom)?;
+        }
+        self.check_tag_prefix(false)
+            .map_err(<toml_edit::de::Error as serde::de::Error>::custom)?;
+        Ok(self)
+    }
+
+    pub fn from_cargo_manifest(content: &str) -> std::result::Result<Self, toml_edit::de::Error> {
+        let manifest = content.parse::<Document>()?;
+        let metadata = manifest
+            .get("package")
+            .and_then(|p| p.get("metadata"))
+            .and_then(|m| m.get("protokollant"));
+        match metadata.cloned().and_then(|m| m.into_table().ok()) {
+            Some(table) => toml_edit::de::from_document::<Self>(Document::from(table))?.validate(),
+            None => Ok(Self::default()),
+        }
+    }
+}
+
+/// Finds the nearest file with the given name in `dir` or one of its parents.
+pub fn find_upwards(dir: &Path, name: &str) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|d| d.join(name))
+        .find(|path| path.exists())
+}
+
+/// Checks that a prerelease identifier like `alpha` can be used in versions.
+pub fn parse_preid(preid: &str) -> std::result::Result<String, String> {
+    let valid = !preid.is_empty() && preid.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    if valid {
+        Ok(preid.into())
+    } else {
+        Err(format!("`{preid}` is not a valid prerelease identifier"))
+    }
+}
+
+fn invalid_config(path: &Path, error: toml_edit::de::Error) -> Error {
+    let message = format!("invalid configuration in {}: {error}", path.display());
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_from_toml() {
+        let config = Config::from_toml(
+            r#"
+            changelog = "docs/CHANGES.md"
+            fragments = "docs/changes"
+            section-order = ["fixed", "added"]
+            preid = "rc"
+            tag-prefix = ""
+            scheme = "calver"
+            calver-format = "YY.0M.MICRO"
+            compare-url = "https://example.com/compare/{previous}...{current}"
+            commit-message = "chore: release {tag}"
+            host = "gitea"
+            build-metadata-in-changelog = true
+            manifests = []
+
+            [python]
+            version-file = "src/pkg/__init__.py"
+
+            [collect]
+            exclude-scopes = ["deps"]
+
+            [[version-files]]
+            path = "README.md"
+            search = "pkg@{version}"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config,
+            Config {
+                changelog: PathBuf::from("docs/CHANGES.md"),
+                fragments: PathBuf::from("docs/changes"),
+                section_order: vec![SectionKind::Fixed, SectionKind::Added],
+                preid: Some("rc".into()),
+                tag_prefix: Some("".into()),
+                scheme: SchemeKind::Calver,
+                calver_format: "YY.0M.MICRO".parse().unwrap(),
+                compare_url: Some("https://example.com/compare/{previous}...{current}".into()),
+                host: Some(Host::Gitea),
+                commit_message: "chore: release {tag}".into(),
+                build_metadata_in_changelog: true,
+                manifests: Some(vec![]),
+                python: PythonConfig {
+                    version_file: Some(PathBuf::from("src/pkg/__init__.py")),
+                },
+                collect: CollectConfig {
+                    scopes: vec![],
+                    exclude_scopes: vec!["deps".into()],
+                },
+                version_files: vec![VersionFile {
+                    path: "README.md".into(),
+                    search: "pkg@{version}".into(),
+                }],
+                monorepo: None,
+            }
+        );
+        let config = Config::from_toml("[monorepo]\npackages = [\"packages/*\"]\n").unwrap();
+        assert_eq!(
+            config.monorepo,
+            Some(MonorepoConfig {
+                packages: vec!["packages/*".into()],
+                tag_prefix: "{package}@v".into(),
+            })
+        );
+        assert!(Config::from_toml("tag-prefix = \"v\"\n[monorepo]\n").is_err());
+        assert!(Config::from_toml("unknown = 1").is_err());
+        assert!(Config::from_toml("preid = \"rc.1\"").is_err());
+        assert!(Config::from_toml("calver-format = \"YYYY.0M\"").is_err());
+    }
+
+    #[test]
+    fn config_from_cargo_manifest() {
+        let config = Config::from_cargo_manifest(
+            r#"
+            [package]
+            name = "foo"
+            version = "1.0.0"
+
+            [package.metadata.protokollant]
+            preid = "beta"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.preid.as_deref(), Some("beta"));
+        assert_eq!(config.changelog, PathBuf::from("CHANGELOG.md"));
+
+        let config = Config::from_cargo_manifest("[package]\nname = \"foo\"\n").unwrap();
+        assert_eq!(config, Config::default());
+    }
+
+    #[test]
+    fn find_root_and_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let package = dir.path().join("packages/a");
+        std::fs::create_dir_all(package.join("src")).unwrap();
+        std::fs::write(dir.path().join("Cargo.lock"), "").unwrap();
+        std::fs::write(dir.path().join("CHANGELOG.md"), "").unwrap();
+
+        assert_eq!(Config::find_root(&package.join("src")), dir.path());
+        std::fs::write(package.join("protokollant.toml"), "").unwrap();
+        assert_eq!(Config::find_root(&package.join("src")), package);
+
+        let lockfile = find_upwards(&package, "Cargo.lock");
+        assert_eq!(lockfile, Some(dir.path().join("Cargo.lock")));
+        assert_eq!(find_upwards(&package, "missing.lock"), None);
+    }
+
+    #[test]
+    fn collect_scopes() {
+        let collect = CollectConfig {
+            scopes: vec!["cli".into(), "core".into()],
+            exclude_scopes: vec!["core".into()],
+        };
+        assert!(collect.includes(Some("cli")));
+        assert!(!collect.includes(Some("core")));
+        assert!(!collect.includes(Some("docs")));
+        assert!(!collect.includes(None));
+        assert!(CollectConfig::default().includes(None));
+    }
+}
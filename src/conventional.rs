@@ -0,0 +1,102 @@
+use crate::model::SectionKind;
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// A commit message following the Conventional Commits specification, like `feat(cli)!: Add x`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConventionalCommit {
+    pub kind: String,
+    pub scope: Option<String>,
+    pub breaking: bool,
+    pub description: String,
+}
+
+impl ConventionalCommit {
+    /// Parses the subject and footers of a commit message, or returns `None` if it is not
+    /// a conventional commit.
+    pub fn parse(message: &str) -> Option<Self> {
+        static SUBJECT: OnceLock<Regex> = OnceLock::new();
+        let subject = SUBJECT.get_or_init(|| {
+            Regex::new(
+                r"^(?<kind>[A-Za-z]+)(?:\((?<scope>[^()]+)\))?(?<breaking>!)?: (?<description>.+)$",
+            )
+            .unwrap()
+        });
+
+        let mut lines = message.lines();
+        let captures = subject.captures(lines.next()?.trim())?;
+        let footer_breaking = lines.any(|line| {
+            line.starts_with("BREAKING CHANGE:") || line.starts_with("BREAKING-CHANGE:")
+        });
+
+        Some(Self {
+            kind: captures["kind"].to_lowercase(),
+            scope: captures
+                .name("scope")
+                .map(|s| s.as_str().trim().to_string()),
+            breaking: captures.name("breaking").is_some() || footer_breaking,
+            description: captures["description"].trim().to_string(),
+        })
+    }
+
+    /// The section the commit belongs to, or `None` if it should not be listed.
+    ///
+    /// Breaking commits of types without a section of their own are listed as changed.
+    pub fn section(&self) -> Option<SectionKind> {
+        match self.kind.as_str() {
+            "feat" => Some(SectionKind::Added),
+            "fix" => Some(SectionKind::Fixed),
+            "perf" => Some(SectionKind::Changed),
+            "deprecate" => Some(SectionKind::Deprecated),
+            "remove" => Some(SectionKind::Removed),
+            "security" => Some(SectionKind::Security),
+            _ if self.breaking => Some(SectionKind::Changed),
+            _ => None,
+        }
+    }
+
+    /// The text of the changelog entry, like `**Breaking:** **cli:** Drop the x option`.
+    pub fn entry_text(&self) -> String {
+        let mut chars = self.description.chars();
+        let mut text = match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect(),
+            None => String::new(),
+        };
+        if let Some(scope) = &self.scope {
+            text = format!("**{scope}:** {text}");
+        }
+        if self.breaking {
+            text = format!("**Breaking:** {text}");
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_conventional_commits() {
+        let commit =
+            ConventionalCommit::parse("feat(cli): add collect command\n\nDetails\n").unwrap();
+        assert_eq!(commit.section(), Some(SectionKind::Added));
+        assert_eq!(commit.entry_text(), "**cli:** Add collect command");
+
+        let commit = ConventionalCommit::parse("fix!: drop support for x").unwrap();
+        assert_eq!(commit.section(), Some(SectionKind::Fixed));
+        assert_eq!(commit.entry_text(), "**Breaking:** Drop support for x");
+
+        let commit =
+            ConventionalCommit::parse("refactor: rewrite y\n\nBREAKING CHANGE: y is gone").unwrap();
+        assert!(commit.breaking);
+        assert_eq!(commit.section(), Some(SectionKind::Changed));
+
+        let commit = ConventionalCommit::parse("chore(deps): bump z").unwrap();
+        assert_eq!(commit.scope.as_deref(), Some("deps"));
+        assert_eq!(commit.section(), None);
+
+        assert_eq!(ConventionalCommit::parse("Merge branch 'main'"), None);
+        assert_eq!(ConventionalCommit::parse("feat:missing space"), None);
+    }
+}
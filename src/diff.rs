@@ -1,56 +1,203 @@
-use colored::Colorize;
-use itertools::Itertools;
+use clap::ValueEnum;
+use colored::{ColoredString, Colorize};
+use std::fmt::{Display, Formatter};
 use std::io::{Result, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone)]
 pub struct FileDiff {
+    path: PathBuf,
     filename: String,
     left: String,
     right: String,
+    deleted: bool,
 }
 
 impl FileDiff {
-    pub fn new(filename: impl Into<String>, left: String, right: String) -> Self {
-        let filename = filename.into();
+    /// A diff of the file at `path`, which is displayed relative to `root`.
+    pub fn new(root: &Path, path: impl Into<PathBuf>, left: String, right: String) -> Self {
+        let path = path.into();
+        let filename = relative_path(root, &path);
         Self {
+            path,
             filename,
             left,
             right,
+            deleted: false,
         }
     }
+
+    /// A diff removing the file with the given content.
+    pub fn deletion(root: &Path, path: impl Into<PathBuf>, left: String) -> Self {
+        Self {
+            deleted: true,
+            ..Self::new(root, path, left, String::new())
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
+
+    pub fn left(&self) -> &str {
+        &self.left
+    }
+
+    pub fn right(&self) -> &str {
+        &self.right
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        self.deleted
+    }
+
+    pub fn is_changed(&self) -> bool {
+        self.deleted || self.left != self.right
+    }
 }
 
-struct DiffEntry<T>(usize, usize, usize, usize, Vec<diff::Result<T>>);
+/// Combines the successive diffs of each file into one, displaying all paths relative to `root`.
+pub fn merge_diffs(root: &Path, diffs: Vec<FileDiff>) -> Vec<FileDiff> {
+    let mut merged = Vec::<FileDiff>::new();
+    for diff in diffs {
+        match merged.iter_mut().find(|m| m.path == diff.path) {
+            Some(existing) => {
+                existing.right = diff.right;
+                existing.deleted = diff.deleted;
+            }
+            None => merged.push(FileDiff {
+                filename: relative_path(root, &diff.path),
+                ..diff
+            }),
+        }
+    }
+    merged
+}
 
-pub fn diff_files<W: Write>(w: &mut W, file_diffs: &[FileDiff]) -> Result<()> {
+/// Displays `path` relative to `root`, going up with `..` if it is outside of it.
+fn relative_path(root: &Path, path: &Path) -> String {
+    if let Ok(relative) = path.strip_prefix(root) {
+        return relative.display().to_string();
+    }
+    let common = root
+        .ancestors()
+        .find(|a| !a.as_os_str().is_empty() && path.starts_with(a));
+    let Some(common) = common else {
+        return path.display().to_string();
+    };
+
+    let mut relative = PathBuf::new();
+    for _ in root.strip_prefix(common).unwrap().components() {
+        relative.push("..");
+    }
+    relative.push(path.strip_prefix(common).unwrap());
+    relative.display().to_string()
+}
+
+/// The number of unchanged lines shown around changes.
+const CONTEXT: usize = 3;
+
+/// How diffs are printed.
+#[derive(ValueEnum, Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum DiffFormat {
+    /// Colored diffs with `before` and `after` headers, meant to be read.
+    #[default]
+    Human,
+    /// Unified diffs with `a/` and `b/` paths, which can be applied with `git apply`.
+    Patch,
+}
+
+/// A hunk of a diff, covering a range of diff lines and the 1-based line ranges of both sides.
+#[derive(Debug, PartialEq, Eq)]
+struct Hunk {
+    lines: Range<usize>,
+    old_start: usize,
+    old_count: usize,
+    new_start: usize,
+    new_count: usize,
+}
+
+impl Display for Hunk {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "@@ -{},{} +{},{} @@",
+            self.old_start, self.old_count, self.new_start, self.new_count
+        )
+    }
+}
+
+pub fn diff_files<W: Write>(w: &mut W, file_diffs: &[FileDiff], format: DiffFormat) -> Result<()> {
     for diff in file_diffs {
-        diff_file(w, diff)?;
+        match format {
+            DiffFormat::Human => write_diff(w, diff, true)?,
+            DiffFormat::Patch => write_patch(w, diff)?,
+        }
     }
     Ok(())
 }
 
-pub fn diff_file<W: Write>(w: &mut W, file_diff: &FileDiff) -> Result<()> {
-    let lines = diff::lines(file_diff.left.trim_end(), file_diff.right.trim_end());
-    let changed_lines = get_changed_lines(&lines);
+/// The diff of a file as it is printed, but without colors.
+pub fn diff_text(file_diff: &FileDiff, format: DiffFormat) -> String {
+    let mut text = Vec::new();
+    match format {
+        DiffFormat::Human => write_diff(&mut text, file_diff, false),
+        DiffFormat::Patch => write_patch(&mut text, file_diff),
+    }
+    .expect("writing to a Vec to succeed");
+    String::from_utf8(text).expect("diff to be valid UTF-8")
+}
+
+fn write_diff<W: Write>(w: &mut W, file_diff: &FileDiff, color: bool) -> Result<()> {
+    let paint = |text: String, style: fn(&str) -> ColoredString| {
+        if color {
+            style(&text).to_string()
+        } else {
+            text
+        }
+    };
 
-    if changed_lines.is_empty() {
+    let left = file_diff.left.trim_end().lines().collect::<Vec<_>>();
+    let right = file_diff.right.trim_end().lines().collect::<Vec<_>>();
+    let lines = diff::slice(&left, &right);
+    let hunks = hunks(&lines);
+
+    if hunks.is_empty() {
+        if file_diff.deleted {
+            let header = format!("--- {} deleted", file_diff.filename);
+            writeln!(w, "{}", paint(header, |s| s.bold()))?;
+        }
         return Ok(());
     }
 
     let filename = &file_diff.filename;
-    writeln!(w, "{}", format!("--- {filename} before").bold())?;
-    writeln!(w, "{}", format!("+++ {filename} after").bold())?;
-    let changed_groups = group_changes(changed_lines, lines.len());
-    let changed_groups = with_diff(changed_groups, lines);
-
-    for DiffEntry(from1, count1, from2, count2, lines) in changed_groups {
-        let string = format!("@@ -{from1},{count1} +{from2},{count2} @@").cyan();
-        writeln!(w, "{}", string)?;
-        for line in lines {
+    let after = if file_diff.deleted {
+        "deleted"
+    } else {
+        "after"
+    };
+    writeln!(
+        w,
+        "{}",
+        paint(format!("--- {filename} before"), |s| s.bold())
+    )?;
+    writeln!(
+        w,
+        "{}",
+        paint(format!("+++ {filename} {after}"), |s| s.bold())
+    )?;
+    for hunk in hunks {
+        writeln!(w, "{}", paint(hunk.to_string(), |s| s.cyan()))?;
+        for line in &lines[hunk.lines] {
             match line {
-                diff::Result::Left(l) => writeln!(w, "{}{}", "-".red(), l.red())?,
+                diff::Result::Left(l) => writeln!(w, "{}", paint(format!("-{l}"), |s| s.red()))?,
                 diff::Result::Both(l, _) => writeln!(w, " {}", l)?,
-                diff::Result::Right(r) => writeln!(w, "{}{}", "+".green(), r.green())?,
+                diff::Result::Right(r) => writeln!(w, "{}", paint(format!("+{r}"), |s| s.green()))?,
             }
         }
     }
@@ -58,65 +205,194 @@ pub fn diff_file<W: Write>(w: &mut W, file_diff: &FileDiff) -> Result<()> {
     Ok(())
 }
 
-fn get_changed_lines(lines: &[diff::Result<&str>]) -> Vec<usize> {
-    lines
-        .iter()
-        .enumerate()
-        .filter(|(_, diff)| !matches!(diff, diff::Result::Both(_, _)))
-        .map(|(index, _)| index)
-        .collect::<Vec<_>>()
-}
+/// Writes a unified diff of the file, marking lines without a final newline like `diff` does.
+fn write_patch<W: Write>(w: &mut W, file_diff: &FileDiff) -> Result<()> {
+    let left = file_diff.left.split_inclusive('\n').collect::<Vec<_>>();
+    let right = file_diff.right.split_inclusive('\n').collect::<Vec<_>>();
+    let lines = diff::slice(&left, &right);
+    let hunks = hunks(&lines);
+
+    let path = file_diff.filename.replace('\\', "/");
+    if hunks.is_empty() {
+        if file_diff.deleted {
+            // An empty file can only be deleted with a git header.
+            writeln!(w, "diff --git a/{path} b/{path}")?;
+            writeln!(w, "deleted file mode 100644")?;
+        }
+        return Ok(());
+    }
 
-fn group_changes(line_numbers: Vec<usize>, len: usize) -> Vec<(usize, usize)> {
-    let mut groups = Vec::new();
-    if let (Some(&first), Some(&last)) = (line_numbers.first(), line_numbers.last()) {
-        let mut filter_pairs = line_numbers
-            .iter()
-            .copied()
-            .tuple_windows()
-            .filter(|(a, b)| b - a > 7)
-            .collect::<Vec<_>>();
-        filter_pairs.insert(0, (0, first));
-        filter_pairs.push((last, 0));
-
-        for ((_, a), (b, _)) in filter_pairs.into_iter().tuple_windows() {
-            groups.push((a.max(3) - 3, b.min(len - 4) + 3));
+    writeln!(w, "--- a/{path}")?;
+    if file_diff.deleted {
+        writeln!(w, "+++ /dev/null")?;
+    } else {
+        writeln!(w, "+++ b/{path}")?;
+    }
+    for hunk in hunks {
+        writeln!(w, "{hunk}")?;
+        for line in &lines[hunk.lines] {
+            let (prefix, text) = match line {
+                diff::Result::Left(l) => ('-', *l),
+                diff::Result::Both(l, _) => (' ', *l),
+                diff::Result::Right(r) => ('+', *r),
+            };
+            write!(w, "{prefix}{text}")?;
+            if !text.ends_with('\n') {
+                writeln!(w, "\n\\ No newline at end of file")?;
+            }
         }
     }
 
-    groups
+    Ok(())
 }
 
-fn with_diff(groups: Vec<(usize, usize)>, lines: Vec<diff::Result<&str>>) -> Vec<DiffEntry<&str>> {
-    let mut result = Vec::new();
+/// Groups the changed lines into hunks with up to three lines of context, merging hunks
+/// whose context would overlap.
+fn hunks<T>(lines: &[diff::Result<T>]) -> Vec<Hunk> {
+    let mut ranges = Vec::<Range<usize>>::new();
+    for (index, line) in lines.iter().enumerate() {
+        if matches!(line, diff::Result::Both(_, _)) {
+            continue;
+        }
+        let start = index.saturating_sub(CONTEXT);
+        let end = (index + CONTEXT + 1).min(lines.len());
+        match ranges.last_mut() {
+            Some(last) if start <= last.end => last.end = end,
+            _ => ranges.push(start..end),
+        }
+    }
 
-    let mut left = 0;
-    let mut right = 0;
+    let is_old = |line: &&diff::Result<T>| !matches!(line, diff::Result::Right(_));
+    let is_new = |line: &&diff::Result<T>| !matches!(line, diff::Result::Left(_));
+    ranges
+        .into_iter()
+        .map(|range| {
+            let old_before = lines[..range.start].iter().filter(is_old).count();
+            let new_before = lines[..range.start].iter().filter(is_new).count();
+            let old_count = lines[range.clone()].iter().filter(is_old).count();
+            let new_count = lines[range.clone()].iter().filter(is_new).count();
+            // Empty ranges start at the line before them, as in `diff -u`.
+            Hunk {
+                lines: range,
+                old_start: old_before + usize::from(old_count > 0),
+                old_count,
+                new_start: new_before + usize::from(new_count > 0),
+                new_count,
+            }
+        })
+        .collect()
+}
 
-    for (from, to) in groups {
-        let mut vec = Vec::new();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::fs::{read_to_string, write};
+    use std::process::{Command, Stdio};
 
-        let from1 = from - right + 1;
-        let from2 = from - left + 1;
+    fn patch(left: &str, right: &str) -> String {
+        let diff = FileDiff::new(Path::new("."), "./CHANGELOG.md", left.into(), right.into());
+        diff_text(&diff, DiffFormat::Patch)
+    }
 
-        for index in from..=to {
-            let line = lines.get(index).unwrap();
-            match line {
-                diff::Result::Left(_) => {
-                    left += 1;
-                }
-                diff::Result::Right(_) => {
-                    right += 1;
+    #[test]
+    fn unified_diff() {
+        let left = (1..=20).map(|i| format!("{i}\n")).collect::<String>();
+        let right = (1..=20)
+            .filter(|&i| i != 15)
+            .map(|i| {
+                if i == 2 {
+                    "two\n".into()
+                } else {
+                    format!("{i}\n")
                 }
-                _ => {}
-            }
-            vec.push(line.clone());
+            })
+            .collect::<String>();
+        assert_eq!(
+            patch(&left, &right),
+            "--- a/CHANGELOG.md\n+++ b/CHANGELOG.md\n\
+            @@ -1,5 +1,5 @@\n 1\n-2\n+two\n 3\n 4\n 5\n\
+            @@ -12,7 +12,6 @@\n 12\n 13\n 14\n-15\n 16\n 17\n 18\n"
+        );
+
+        assert_eq!(
+            patch("a\nb", "a\nc\n"),
+            "--- a/CHANGELOG.md\n+++ b/CHANGELOG.md\n\
+            @@ -1,2 +1,2 @@\n a\n-b\n\\ No newline at end of file\n+c\n"
+        );
+        assert_eq!(
+            patch("", "a\n"),
+            "--- a/CHANGELOG.md\n+++ b/CHANGELOG.md\n@@ -0,0 +1,1 @@\n+a\n"
+        );
+        assert_eq!(patch("a\n", "a\n"), "");
+
+        let deletion = FileDiff::deletion(Path::new("."), "./a.md", "a\n".into());
+        assert_eq!(
+            diff_text(&deletion, DiffFormat::Patch),
+            "--- a/a.md\n+++ /dev/null\n@@ -1,1 +0,0 @@\n-a\n"
+        );
+    }
+
+    /// Applies the patch of a diff with `git apply` and returns the resulting file content,
+    /// or `None` if the file was deleted.
+    fn apply_patch(left: &str, right: &str, deleted: bool) -> Option<String> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        write(&path, left).unwrap();
+        let diff = if deleted {
+            FileDiff::deletion(dir.path(), &path, left.into())
+        } else {
+            FileDiff::new(dir.path(), &path, left.into(), right.into())
+        };
+
+        let patch = diff_text(&diff, DiffFormat::Patch);
+        if patch.is_empty() {
+            return Some(left.into());
         }
 
-        let count1 = to - right - from1 + 2;
-        let count2 = to - left - from2 + 2;
+        let mut git = Command::new("git")
+            .args(["apply", "--whitespace=nowarn", "-"])
+            .current_dir(dir.path())
+            .stdin(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        git.stdin
+            .take()
+            .unwrap()
+            .write_all(patch.as_bytes())
+            .unwrap();
+        let output = git.wait_with_output().unwrap();
+        assert!(
+            output.status.success(),
+            "git apply failed: {}\n{patch}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        read_to_string(&path).ok()
+    }
+
+    fn content() -> impl Strategy<Value = String> {
+        let line = prop::sample::select(vec!["a", "b", "c", "", "  d", "## [1.0.0]"]);
+        (prop::collection::vec(line, 0..24), any::<bool>()).prop_map(|(lines, newline)| {
+            let mut content = lines.join("\n");
+            if newline && !content.is_empty() {
+                content.push('\n');
+            }
+            content
+        })
+    }
 
-        result.push(DiffEntry(from1, count1, from2, count2, vec));
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn patch_applies(left in content(), right in content()) {
+            prop_assert_eq!(apply_patch(&left, &right, false), Some(right));
+        }
+
+        #[test]
+        fn deletion_patch_applies(left in content()) {
+            prop_assert_eq!(apply_patch(&left, "", true), None);
+        }
     }
-    result
 }
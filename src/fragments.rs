@@ -0,0 +1,210 @@
+use crate::model::SectionKind;
+use std::fs::{create_dir_all, read_dir, read_to_string, write};
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+
+/// A changelog entry kept in its own file until the next release.
+///
+/// Fragments are either placed in a directory named after their section, like
+/// `.changes/fixed/crash.md`, or declare it in a frontmatter:
+///
+/// ```text
+/// ---
+/// type: fixed
+/// ---
+/// Fix crash on startup
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fragment {
+    pub path: PathBuf,
+    pub content: String,
+    pub kind: SectionKind,
+    pub entries: Vec<String>,
+}
+
+impl Fragment {
+    fn parse(path: PathBuf, content: String, kind: Option<SectionKind>) -> Result<Self> {
+        let invalid = |message: &str| {
+            let message = format!("invalid fragment {}: {message}", path.display());
+            Error::new(ErrorKind::InvalidData, message)
+        };
+
+        let (frontmatter, body) = match content.strip_prefix("---\n") {
+            Some(rest) => match rest.split_once("\n---\n") {
+                Some((frontmatter, body)) => (Some(frontmatter), body),
+                None => return Err(invalid("unterminated frontmatter")),
+            },
+            None => (None, content.as_str()),
+        };
+
+        let declared = frontmatter
+            .into_iter()
+            .flat_map(str::lines)
+            .filter_map(|line| line.split_once(':'))
+            .find(|(key, _)| key.trim() == "type")
+            .map(|(_, value)| {
+                SectionKind::from_title(value)
+                    .ok_or_else(|| invalid(&format!("unknown type `{}`", value.trim())))
+            })
+            .transpose()?;
+        let kind = declared
+            .or(kind)
+            .ok_or_else(|| invalid("missing `type` in frontmatter"))?;
+
+        let entries = split_entries(body);
+        if entries.is_empty() {
+            return Err(invalid("no entry text"));
+        }
+
+        Ok(Self {
+            path,
+            content,
+            kind,
+            entries,
+        })
+    }
+}
+
+/// Splits a fragment body into entries, one per bullet or a single one without bullets.
+fn split_entries(body: &str) -> Vec<String> {
+    let mut entries = Vec::<String>::new();
+    for line in body.trim().lines() {
+        match line
+            .strip_prefix(['-', '*', '+'])
+            .and_then(|l| l.strip_prefix(' '))
+        {
+            Some(text) => entries.push(text.trim().into()),
+            None => match entries.last_mut() {
+                Some(entry) if !line.trim().is_empty() => {
+                    entry.push('\n');
+                    entry.push_str(line.trim());
+                }
+                Some(_) => {}
+                None => entries.push(line.trim().into()),
+            },
+        }
+    }
+    entries
+}
+
+/// Reads all fragments in the given directory, sorted by path.
+pub fn read_fragments(dir: &Path) -> Result<Vec<Fragment>> {
+    let mut fragments = Vec::new();
+    if !dir.is_dir() {
+        return Ok(fragments);
+    }
+
+    for path in markdown_files(dir)? {
+        fragments.push(Fragment::parse(path.clone(), read_to_string(&path)?, None)?);
+    }
+    for kind in SectionKind::ALL {
+        let kind_dir = dir.join(kind.title().to_lowercase());
+        if kind_dir.is_dir() {
+            for path in markdown_files(&kind_dir)? {
+                let content = read_to_string(&path)?;
+                fragments.push(Fragment::parse(path, content, Some(kind))?);
+            }
+        }
+    }
+
+    fragments.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(fragments)
+}
+
+fn markdown_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in read_dir(dir)? {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let is_fragment = path.is_file()
+            && name.ends_with(".md")
+            && !name.starts_with('.')
+            && !name.eq_ignore_ascii_case("README.md");
+        if is_fragment {
+            paths.push(path);
+        }
+    }
+    Ok(paths)
+}
+
+/// Writes a new fragment to `<dir>/<type>/<name>.md`, naming it after the entry text.
+pub fn write_fragment(dir: &Path, kind: SectionKind, text: &str) -> Result<PathBuf> {
+    let kind_dir = dir.join(kind.title().to_lowercase());
+    create_dir_all(&kind_dir)?;
+
+    let slug = text
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .take(6)
+        .collect::<Vec<_>>()
+        .join("-");
+    let slug = if slug.is_empty() {
+        "change".into()
+    } else {
+        slug
+    };
+
+    let mut path = kind_dir.join(format!("{slug}.md"));
+    let mut index = 1;
+    while path.exists() {
+        index += 1;
+        path = kind_dir.join(format!("{slug}-{index}.md"));
+    }
+    write(&path, format!("{}\n", text.trim_end()))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_and_write_fragments() {
+        let dir = tempfile::tempdir().unwrap();
+        let changes = dir.path().join(".changes");
+
+        let path = write_fragment(&changes, SectionKind::Fixed, "Fix crash on `start`").unwrap();
+        assert_eq!(path, changes.join("fixed/fix-crash-on-start.md"));
+        let path = write_fragment(&changes, SectionKind::Fixed, "Fix crash on start").unwrap();
+        assert_eq!(path, changes.join("fixed/fix-crash-on-start-2.md"));
+
+        write(
+            changes.join("api.md"),
+            "---\ntype: Removed\n---\n\n- Remove a\n- Remove b\n  over two lines\n",
+        )
+        .unwrap();
+        write(changes.join("README.md"), "Add fragments here\n").unwrap();
+
+        let fragments = read_fragments(&changes).unwrap();
+        let entries = fragments
+            .iter()
+            .map(|f| (f.kind, f.entries.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            vec![
+                (
+                    SectionKind::Removed,
+                    vec!["Remove a".into(), "Remove b\nover two lines".into()]
+                ),
+                (SectionKind::Fixed, vec!["Fix crash on start".into()]),
+                (SectionKind::Fixed, vec!["Fix crash on `start`".into()]),
+            ]
+        );
+
+        assert!(read_fragments(&dir.path().join("missing"))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn report_invalid_fragments() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path().join("a.md"), "Fix a\n").unwrap();
+        assert!(read_fragments(dir.path()).is_err());
+
+        write(dir.path().join("a.md"), "---\ntype: misc\n---\nFix a\n").unwrap();
+        assert!(read_fragments(dir.path()).is_err());
+    }
+}
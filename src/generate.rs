@@ -1,4 +1,4 @@
-use crate::model::{Changelog, Ref, Release};
+use crate::model::{Changelog, Entry, Ref, Release, Section};
 
 pub fn generate_str(model: &Changelog) -> String {
     let mut str = String::new();
@@ -6,50 +6,101 @@ pub fn generate_str(model: &Changelog) -> String {
     str.push_str(&model.intro);
 
     for release in &model.releases {
-        let Release {
-            version,
-            date,
-            intro,
-            added,
-            removed,
-            changed,
-            fixed,
-        } = release;
-        if let Some(date) = date {
-            str.push_str(&format!("## [{version}] - {date}\n\n"));
-        } else {
-            str.push_str(&format!("## [{version}]\n\n"));
-        }
-        str.push_str(intro);
+        generate_release(&mut str, release);
+    }
 
-        generate_section(&mut str, "Added", added);
-        generate_section(&mut str, "Fixed", fixed);
-        generate_section(&mut str, "Changed", changed);
-        generate_section(&mut str, "Removed", removed);
+    // Link reference definitions cannot interrupt a paragraph, so new ones need a blank line
+    let inserted = model.refs.first().is_some_and(|r| r.source.is_none());
+    if inserted && !str.is_empty() {
+        while !str.ends_with("\n\n") {
+            str.push('\n');
+        }
     }
 
     for reference in &model.refs {
-        let Ref { anchor, href } = reference;
-        str.push_str(&format!("[{anchor}]: {href}\n"));
+        let Ref {
+            anchor,
+            href,
+            source,
+        } = reference;
+        let key = (anchor.clone(), href.clone());
+        match source.as_ref().and_then(|s| s.text_for(&key)) {
+            Some(text) => str.push_str(text),
+            None => str.push_str(&format!("[{anchor}]: {href}\n")),
+        }
     }
 
     str
 }
 
-fn generate_section(target: &mut String, heading: &str, items: &Vec<String>) {
-    if items.is_empty() {
-        return;
+fn generate_release(target: &mut String, release: &Release) {
+    let Release {
+        version,
+        date,
+        intro,
+        sections,
+        heading,
+    } = release;
+    let key = (version.clone(), *date);
+    match heading.as_ref().and_then(|s| s.text_for(&key)) {
+        Some(text) => target.push_str(text),
+        None => {
+            if let Some(date) = date {
+                target.push_str(&format!("## [{version}] - {date}\n\n"));
+            } else {
+                target.push_str(&format!("## [{version}]\n\n"));
+            }
+        }
     }
+    target.push_str(intro);
 
-    target.push_str(&format!("### {heading}\n\n"));
-    for item in items {
-        let bullet = "- ".to_string()
-            + item
-                .lines()
-                .fold(String::new(), |a, b| a + "\n  " + b)
-                .trim_start()
-            + "\n";
-        target.push_str(&bullet);
+    for section in sections {
+        generate_section(target, section);
     }
-    target.push('\n');
+}
+
+pub fn generate_release_body(release: &Release) -> String {
+    let mut str = release.intro.clone();
+    for section in &release.sections {
+        generate_section(&mut str, section);
+    }
+
+    let len = str.trim_end().len();
+    str.truncate(len);
+    str.push('\n');
+    str
+}
+
+fn generate_section(target: &mut String, section: &Section) {
+    let Section {
+        title,
+        entries,
+        trailer,
+        heading,
+    } = section;
+    match heading.as_ref().and_then(|s| s.text_for(title)) {
+        Some(text) => target.push_str(text),
+        None => target.push_str(&format!("### {title}\n\n")),
+    }
+
+    for entry in entries {
+        generate_entry(target, entry);
+    }
+    target.push_str(trailer);
+}
+
+fn generate_entry(target: &mut String, entry: &Entry) {
+    let Entry { text, source } = entry;
+    if let Some(source) = source.as_ref().and_then(|s| s.text_for(text)) {
+        target.push_str(source);
+        return;
+    }
+
+    let bullet = "- ".to_string()
+        + text
+            .lines()
+            .fold(String::new(), |a, b| a + "\n  " + b)
+            .trim_start()
+        + "\n";
+    target.push_str(&bullet);
 }
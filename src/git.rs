@@ -0,0 +1,203 @@
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A local git repository, driven through the `git` binary.
+#[derive(Debug, Clone)]
+pub struct Git {
+    dir: PathBuf,
+}
+
+impl Git {
+    /// Opens the repository containing the given directory.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self> {
+        let git = Self {
+            dir: dir.as_ref().to_path_buf(),
+        };
+        git.run(&["rev-parse", "--git-dir"])
+            .map_err(|e| Error::new(ErrorKind::NotFound, format!("not a git repository: {e}")))?;
+        Ok(git)
+    }
+
+    /// Whether the working tree has uncommitted or untracked changes.
+    pub fn is_dirty(&self) -> Result<bool> {
+        let status = self.run(&["status", "--porcelain"])?;
+        Ok(!status.trim().is_empty())
+    }
+
+    pub fn tag_exists(&self, tag: &str) -> Result<bool> {
+        let tags = self.run(&["tag", "--list", tag])?;
+        Ok(tags.lines().any(|line| line == tag))
+    }
+
+    /// The URL of the given remote, or `None` if there is no such remote.
+    pub fn remote_url(&self, remote: &str) -> Option<String> {
+        let url = self.run(&["remote", "get-url", remote]).ok()?;
+        Some(url.trim().to_string())
+    }
+
+    /// The default branch of the given remote, falling back to `main` if it is unknown.
+    pub fn default_branch(&self, remote: &str) -> String {
+        let head = format!("refs/remotes/{remote}/HEAD");
+        match self.run(&["symbolic-ref", "--short", &head]) {
+            Ok(branch) => {
+                let branch = branch.trim();
+                let prefix = format!("{remote}/");
+                branch.strip_prefix(&prefix).unwrap_or(branch).to_string()
+            }
+            Err(_) => "main".into(),
+        }
+    }
+
+    /// Returns the messages of all commits after the given tag, oldest first.
+    pub fn messages_since(&self, tag: Option<&str>) -> Result<Vec<String>> {
+        let range = match tag {
+            Some(tag) => format!("{tag}..HEAD"),
+            None => "HEAD".into(),
+        };
+        let log = self.run(&["log", "--reverse", "--format=%B%x00", &range])?;
+        Ok(log
+            .split('\0')
+            .map(|message| message.trim().to_string())
+            .filter(|message| !message.is_empty())
+            .collect())
+    }
+
+    /// Commits the given files, leaving all other changes untouched.
+    pub fn commit(&self, files: &[&Path], message: &str) -> Result<()> {
+        let files = files
+            .iter()
+            .map(|file| file.to_string_lossy())
+            .collect::<Vec<_>>();
+
+        let mut add = vec!["add", "--"];
+        add.extend(files.iter().map(|file| file.as_ref()));
+        self.run(&add)?;
+
+        let mut commit = vec!["commit", "--quiet", "--message", message, "--"];
+        commit.extend(files.iter().map(|file| file.as_ref()));
+        self.run(&commit)?;
+        Ok(())
+    }
+
+    /// Creates an annotated tag on the current commit.
+    pub fn tag(&self, tag: &str, message: &str) -> Result<()> {
+        self.run(&["tag", "--annotate", tag, "--message", message])?;
+        Ok(())
+    }
+
+    fn run(&self, args: &[&str]) -> Result<String> {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(&self.dir)
+            .output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let message = format!("`git {}` failed: {}", args.join(" "), stderr.trim());
+            return Err(Error::other(message));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+/// Expands a commit message template with the `{version}` and `{tag}` of a release.
+pub fn commit_message(template: &str, version: &str, tag: &str) -> String {
+    template.replace("{version}", version).replace("{tag}", tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::write;
+    use tempfile::TempDir;
+
+    fn init() -> (TempDir, Git) {
+        let dir = tempfile::tempdir().unwrap();
+        let git = Git {
+            dir: dir.path().to_path_buf(),
+        };
+        git.run(&["init", "--quiet"]).unwrap();
+        git.run(&["config", "user.name", "Test"]).unwrap();
+        git.run(&["config", "user.email", "test@example.com"])
+            .unwrap();
+        git.run(&["config", "commit.gpgsign", "false"]).unwrap();
+        git.run(&["config", "tag.gpgsign", "false"]).unwrap();
+        write(dir.path().join("CHANGELOG.md"), "# Changelog\n").unwrap();
+        git.commit(&[Path::new("CHANGELOG.md")], "Initial commit")
+            .unwrap();
+        (dir, git)
+    }
+
+    #[test]
+    fn open_repository() {
+        let (dir, _) = init();
+        assert!(Git::open(dir.path()).is_ok());
+
+        let dir = tempfile::tempdir().unwrap();
+        let error = Git::open(dir.path()).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn commit_and_tag() {
+        let (dir, git) = init();
+        assert!(!git.is_dirty().unwrap());
+
+        write(
+            dir.path().join("CHANGELOG.md"),
+            "# Changelog\n\n## [1.0.0]\n",
+        )
+        .unwrap();
+        write(dir.path().join("notes.txt"), "unrelated\n").unwrap();
+        assert!(git.is_dirty().unwrap());
+
+        let message = commit_message("Release {version}", "1.0.0", "v1.0.0");
+        git.commit(&[Path::new("CHANGELOG.md")], &message).unwrap();
+        assert!(!git.tag_exists("v1.0.0").unwrap());
+        git.tag("v1.0.0", &message).unwrap();
+        assert!(git.tag_exists("v1.0.0").unwrap());
+        assert!(git.tag("v1.0.0", &message).is_err());
+
+        let subject = git.run(&["log", "-1", "--format=%s"]).unwrap();
+        assert_eq!(subject.trim(), "Release 1.0.0");
+        let status = git.run(&["status", "--porcelain"]).unwrap();
+        assert_eq!(status.trim(), "?? notes.txt");
+    }
+
+    #[test]
+    fn messages_since_tag() {
+        let (dir, git) = init();
+        git.tag("v1.0.0", "Release 1.0.0").unwrap();
+        for (index, message) in ["feat: add a", "fix: fix b\n\nCloses #1"]
+            .iter()
+            .enumerate()
+        {
+            write(dir.path().join("CHANGELOG.md"), index.to_string()).unwrap();
+            git.commit(&[Path::new("CHANGELOG.md")], message).unwrap();
+        }
+
+        let messages = git.messages_since(Some("v1.0.0")).unwrap();
+        assert_eq!(messages, vec!["feat: add a", "fix: fix b\n\nCloses #1"]);
+        assert_eq!(git.messages_since(None).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn remote_and_default_branch() {
+        let (_dir, git) = init();
+        assert_eq!(git.remote_url("origin"), None);
+        assert_eq!(git.default_branch("origin"), "main");
+
+        let url = "git@github.com:ksm2/protokollant.git";
+        git.run(&["remote", "add", "origin", url]).unwrap();
+        git.run(&["update-ref", "refs/remotes/origin/trunk", "HEAD"])
+            .unwrap();
+        git.run(&[
+            "symbolic-ref",
+            "refs/remotes/origin/HEAD",
+            "refs/remotes/origin/trunk",
+        ])
+        .unwrap();
+        assert_eq!(git.remote_url("origin").as_deref(), Some(url));
+        assert_eq!(git.default_branch("origin"), "trunk");
+    }
+}
@@ -1,15 +1,243 @@
+use crate::diff::{diff_text, DiffFormat, FileDiff};
+use crate::manifests::ManifestType;
+use crate::model::{Release, Version};
+use crate::scheme::Scheme;
 use serde::Serialize;
+use std::fmt::{Display, Formatter};
 
+/// The version of the JSON output of releases, incremented on incompatible changes.
+///
+/// The output is described by the JSON Schema in `schema/release.schema.json`.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The JSON output of a release.
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Json {
-    pub version: String,
-    pub previous_version: String,
+    pub schema_version: u32,
+    /// Whether the files were left untouched because of `--diff`.
+    pub dry_run: bool,
+    #[serde(flatten)]
+    pub release: JsonOutcome,
+    pub files: Vec<JsonFile>,
+}
+
+/// The JSON output of releasing the packages of a monorepo.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JsonPackages {
+    pub schema_version: u32,
+    pub dry_run: bool,
     pub bump: bool,
+    pub packages: Vec<JsonPackage>,
+    pub files: Vec<JsonFile>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JsonPackage {
+    pub name: String,
+    /// The directory of the package, relative to the repository root.
+    pub path: String,
+    #[serde(flatten)]
+    pub release: JsonOutcome,
+}
+
+/// The outcome of releasing a single changelog.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JsonOutcome {
+    pub bump: bool,
+    pub version: String,
+    /// The latest version before the release, unless it is the first release.
+    pub previous_version: Option<String>,
+    pub tag: String,
+    /// The date of the release, if it was released.
+    pub date: Option<String>,
+    /// The URL of the new changelog reference, if there is one.
+    pub url: Option<String>,
+    /// The notes of the release, if it was released.
+    pub notes: Option<JsonRelease>,
+    pub manifests: Vec<ManifestType>,
+}
+
+/// A file that is changed by the release.
+#[derive(Debug, Serialize)]
+pub struct JsonFile {
+    pub path: String,
+    pub status: JsonFileStatus,
+    /// The changes of the file, in the format selected with `--format`.
+    pub diff: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JsonFileStatus {
+    Modified,
+    Deleted,
+}
+
+impl JsonFile {
+    /// The changed files among the given diffs.
+    pub fn changed(diffs: &[FileDiff], format: DiffFormat) -> Vec<Self> {
+        diffs
+            .iter()
+            .filter(|diff| diff.is_changed())
+            .map(|diff| Self {
+                path: diff.filename().to_string(),
+                status: if diff.is_deleted() {
+                    JsonFileStatus::Deleted
+                } else {
+                    JsonFileStatus::Modified
+                },
+                diff: diff_text(diff, format),
+            })
+            .collect()
+    }
+}
+
+impl Display for Json {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&serde_json::to_string(self).unwrap())
+    }
+}
+
+impl Display for JsonPackages {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&serde_json::to_string(self).unwrap())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JsonRelease {
+    pub version: String,
+    pub date: Option<String>,
+    pub sections: Vec<JsonSection>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JsonSection {
+    pub title: String,
+    pub entries: Vec<String>,
+}
+
+impl JsonRelease {
+    /// The release with its version formatted by the scheme.
+    pub fn new(release: &Release, scheme: &Scheme) -> Self {
+        let version = match &release.version {
+            Version::Unreleased => release.version.to_string(),
+            Version::Released(version) => scheme.format(version),
+        };
+        Self {
+            version,
+            date: release.date.map(|d| d.to_string()),
+            sections: release
+                .sections
+                .iter()
+                .map(|s| JsonSection {
+                    title: s.title.clone(),
+                    entries: s.entries.iter().map(|e| e.text.clone()).collect(),
+                })
+                .collect(),
+        }
+    }
 }
 
-impl ToString for Json {
-    fn to_string(&self) -> String {
-        serde_json::to_string(self).unwrap()
+impl Display for JsonRelease {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&serde_json::to_string(self).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use std::path::Path;
+
+    const SCHEMA: &str = include_str!("../schema/release.schema.json");
+
+    /// Checks that the object has all required and only the declared properties of a definition.
+    fn assert_matches(value: &Value, schema: &Value, definition: &str) {
+        let definition = &schema["$defs"][definition];
+        let mut parts = vec![definition];
+        for reference in definition["allOf"].as_array().into_iter().flatten() {
+            let name = reference["$ref"].as_str().unwrap();
+            parts.push(&schema["$defs"][name.trim_start_matches("#/$defs/")]);
+        }
+
+        let object = value.as_object().unwrap();
+        for part in &parts {
+            for required in part["required"].as_array().unwrap() {
+                let required = required.as_str().unwrap();
+                assert!(object.contains_key(required), "missing `{required}`");
+            }
+        }
+        for key in object.keys() {
+            let declared = parts
+                .iter()
+                .any(|part| part["properties"].get(key).is_some());
+            assert!(declared, "`{key}` is not declared in the schema");
+        }
+    }
+
+    fn outcome() -> JsonOutcome {
+        JsonOutcome {
+            bump: true,
+            version: "1.1.0".into(),
+            previous_version: Some("1.0.0".into()),
+            tag: "v1.1.0".into(),
+            date: Some("2023-03-15".into()),
+            url: Some("https://example.com/compare/v1.0.0...v1.1.0".into()),
+            notes: Some(JsonRelease {
+                version: "1.1.0".into(),
+                date: Some("2023-03-15".into()),
+                sections: vec![JsonSection {
+                    title: "Added".into(),
+                    entries: vec!["Add a".into()],
+                }],
+            }),
+            manifests: vec![ManifestType::Cargo],
+        }
+    }
+
+    #[test]
+    fn output_matches_schema() {
+        let schema = serde_json::from_str::<Value>(SCHEMA).unwrap();
+        let files = || {
+            let diff = FileDiff::deletion(Path::new("."), "./.changes/a.md", "Add a\n".into());
+            JsonFile::changed(&[diff], DiffFormat::Patch)
+        };
+
+        let json = serde_json::to_value(Json {
+            schema_version: SCHEMA_VERSION,
+            dry_run: false,
+            release: outcome(),
+            files: files(),
+        })
+        .unwrap();
+        assert_matches(&json, &schema, "single");
+        assert_matches(&json["notes"], &schema, "notes");
+        assert_matches(&json["files"][0], &schema, "file");
+        assert_eq!(
+            json["schemaVersion"],
+            schema["$defs"]["single"]["properties"]["schemaVersion"]["const"]
+        );
+        assert_eq!(json["files"][0]["path"], ".changes/a.md");
+        assert_eq!(json["files"][0]["status"], "deleted");
+
+        let json = serde_json::to_value(JsonPackages {
+            schema_version: SCHEMA_VERSION,
+            dry_run: true,
+            bump: true,
+            packages: vec![JsonPackage {
+                name: "a".into(),
+                path: "crates/a".into(),
+                release: outcome(),
+            }],
+            files: files(),
+        })
+        .unwrap();
+        assert_matches(&json, &schema, "monorepo");
+        assert_matches(&json["packages"][0], &schema, "package");
     }
 }
@@ -0,0 +1,327 @@
+use crate::model::{cmp_precedence, parse_version, Changelog, Version};
+use crate::parser::ParseError;
+use crate::scheme::Scheme;
+use clap::ValueEnum;
+use colored::Colorize;
+use semver::Version as SemVer;
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use time::Date;
+
+#[derive(ValueEnum, Debug, Copy, Clone, Default)]
+pub enum LintFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProblemKind {
+    UnreleasedNotFirst,
+    UnorderedVersion,
+    DuplicateVersion,
+    FutureDate,
+    UnorderedDate,
+    MissingRef,
+    UnknownRef,
+    EmptySection,
+    ParseError,
+}
+
+impl ProblemKind {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ProblemKind::UnreleasedNotFirst => "unreleased-not-first",
+            ProblemKind::UnorderedVersion => "unordered-version",
+            ProblemKind::DuplicateVersion => "duplicate-version",
+            ProblemKind::FutureDate => "future-date",
+            ProblemKind::UnorderedDate => "unordered-date",
+            ProblemKind::MissingRef => "missing-ref",
+            ProblemKind::UnknownRef => "unknown-ref",
+            ProblemKind::EmptySection => "empty-section",
+            ProblemKind::ParseError => "parse-error",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Problem {
+    #[serde(rename = "code")]
+    pub kind: ProblemKind,
+    pub message: String,
+}
+
+impl Problem {
+    fn new(kind: ProblemKind, message: String) -> Self {
+        Self { kind, message }
+    }
+
+    /// The problem of a changelog that cannot be parsed at all.
+    pub fn parse_error(error: &ParseError) -> Self {
+        let message = format!(
+            "{}:{}:{}: {}",
+            error.path, error.line, error.column, error.message
+        );
+        Self::new(ProblemKind::ParseError, message)
+    }
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let label = format!("error[{}]", self.kind.code());
+        write!(f, "{}: {}", label.red().bold(), self.message)
+    }
+}
+
+/// Checks the changelog, formatting and comparing versions following the scheme.
+pub fn lint(changelog: &Changelog, today: Date, scheme: &Scheme) -> Vec<Problem> {
+    let mut problems = Vec::new();
+
+    lint_unreleased(changelog, &mut problems);
+    lint_versions(changelog, scheme, &mut problems);
+    lint_dates(changelog, today, &mut problems);
+    lint_refs(changelog, scheme, &mut problems);
+    lint_sections(changelog, &mut problems);
+
+    problems
+}
+
+fn lint_unreleased(changelog: &Changelog, problems: &mut Vec<Problem>) {
+    let positions = changelog
+        .releases
+        .iter()
+        .enumerate()
+        .filter(|(_, r)| r.version == Version::Unreleased)
+        .map(|(position, _)| position)
+        .collect::<Vec<_>>();
+    if positions.first().is_some_and(|p| *p > 0) {
+        problems.push(Problem::new(
+            ProblemKind::UnreleasedNotFirst,
+            "The [Unreleased] section must be the first release".into(),
+        ));
+    }
+    if positions.len() > 1 {
+        problems.push(Problem::new(
+            ProblemKind::UnreleasedNotFirst,
+            format!(
+                "The changelog has {} [Unreleased] sections, only the first release may be unreleased",
+                positions.len()
+            ),
+        ));
+    }
+}
+
+fn lint_versions(changelog: &Changelog, scheme: &Scheme, problems: &mut Vec<Problem>) {
+    let mut seen = Vec::<&SemVer>::new();
+    let mut previous: Option<&SemVer> = None;
+    for version in released_versions(changelog) {
+        let duplicate = seen
+            .iter()
+            .any(|v| cmp_precedence(v, version) == Ordering::Equal);
+        let formatted = scheme.format(version);
+        if duplicate {
+            problems.push(Problem::new(
+                ProblemKind::DuplicateVersion,
+                format!("Release {formatted} is listed more than once"),
+            ));
+        } else if let Some(previous) =
+            previous.filter(|p| cmp_precedence(p, version) == Ordering::Less)
+        {
+            problems.push(Problem::new(
+                ProblemKind::UnorderedVersion,
+                format!(
+                    "Release {formatted} must be listed before {}",
+                    scheme.format(previous)
+                ),
+            ));
+        }
+        seen.push(version);
+        previous = Some(version);
+    }
+}
+
+fn lint_dates(changelog: &Changelog, today: Date, problems: &mut Vec<Problem>) {
+    let mut previous: Option<(&Version, Date)> = None;
+    for release in &changelog.releases {
+        let Some(date) = release.date else {
+            continue;
+        };
+        let version = &release.version;
+        if date > today {
+            problems.push(Problem::new(
+                ProblemKind::FutureDate,
+                format!("Release {version} is dated in the future ({date})"),
+            ));
+        }
+        if let Some((previous_version, previous_date)) = previous {
+            if previous_date < date {
+                problems.push(Problem::new(
+                    ProblemKind::UnorderedDate,
+                    format!(
+                        "Release {version} ({date}) is dated after release {previous_version} ({previous_date})"
+                    ),
+                ));
+            }
+        }
+        previous = Some((version, date));
+    }
+}
+
+fn lint_refs(changelog: &Changelog, scheme: &Scheme, problems: &mut Vec<Problem>) {
+    let anchors = changelog
+        .refs
+        .iter()
+        .filter_map(|r| parse_version(&r.anchor).ok())
+        .collect::<HashSet<_>>();
+    let mut seen = HashSet::new();
+    for version in released_versions(changelog).filter(|v| seen.insert(*v)) {
+        if !anchors.contains(version) {
+            let version = scheme.format(version);
+            problems.push(Problem::new(
+                ProblemKind::MissingRef,
+                format!("Release {version} has no [{version}] reference"),
+            ));
+        }
+    }
+
+    let versions = released_versions(changelog).collect::<HashSet<_>>();
+    for reference in &changelog.refs {
+        let Ok(version) = parse_version(&reference.anchor) else {
+            continue;
+        };
+        if !versions.contains(&version) {
+            problems.push(Problem::new(
+                ProblemKind::UnknownRef,
+                format!(
+                    "Reference [{}] points to a missing release",
+                    reference.anchor
+                ),
+            ));
+        }
+    }
+}
+
+fn lint_sections(changelog: &Changelog, problems: &mut Vec<Problem>) {
+    for release in &changelog.releases {
+        for section in release.sections.iter().filter(|s| s.entries.is_empty()) {
+            problems.push(Problem::new(
+                ProblemKind::EmptySection,
+                format!(
+                    "Section {} of release {} has no entries",
+                    section.title, release.version
+                ),
+            ));
+        }
+    }
+}
+
+fn released_versions(changelog: &Changelog) -> impl Iterator<Item = &SemVer> {
+    changelog.releases.iter().filter_map(|r| match &r.version {
+        Version::Unreleased => None,
+        Version::Released(v) => Some(v),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_str;
+    use time::Month;
+
+    fn codes(content: &str) -> Vec<&'static str> {
+        codes_with_scheme(content, &Scheme::SemVer)
+    }
+
+    fn codes_with_scheme(content: &str, scheme: &Scheme) -> Vec<&'static str> {
+        let changelog = parse_str(content).unwrap();
+        let today = Date::from_calendar_date(2023, Month::March, 20).unwrap();
+        lint(&changelog, today, scheme)
+            .iter()
+            .map(|p| p.kind.code())
+            .collect()
+    }
+
+    #[test]
+    fn lint_valid() {
+        let content = "# Changelog\n\n\
+            ## [Unreleased]\n\n\
+            ## [1.1.0] - 2023-03-15\n\n### Added\n\n- Add a\n\n\
+            ## [1.0.0] - 2023-03-01\n\n### Added\n\n- Add b\n\n\
+            [unreleased]: https://example.com/compare/v1.1.0...HEAD\n\
+            [1.1.0]: https://example.com/compare/v1.0.0...v1.1.0\n\
+            [1.0.0]: https://example.com/releases/tag/v1.0.0\n";
+        assert!(codes(content).is_empty());
+    }
+
+    #[test]
+    fn lint_problems() {
+        let content = "# Changelog\n\n\
+            ## [1.0.0] - 2023-03-01\n\n### Added\n\n\
+            ## [Unreleased]\n\n\
+            ## [1.1.0] - 2023-03-25\n\n### Added\n\n- Add a\n\n\
+            ## [1.1.0] - 2023-03-15\n\n### Fixed\n\n- Fix b\n\n\
+            [1.0.0]: https://example.com/releases/tag/v1.0.0\n\
+            [0.9.0]: https://example.com/releases/tag/v0.9.0\n";
+        assert_eq!(
+            codes(content),
+            vec![
+                "unreleased-not-first",
+                "unordered-version",
+                "duplicate-version",
+                "future-date",
+                "unordered-date",
+                "missing-ref",
+                "unknown-ref",
+                "empty-section",
+            ]
+        );
+    }
+
+    #[test]
+    fn lint_second_unreleased() {
+        let content = "# Changelog\n\n\
+            ## [Unreleased]\n\n\
+            ## [1.0.0] - 2023-03-01\n\n### Added\n\n- Add a\n\n\
+            ## [Unreleased]\n\n\
+            [1.0.0]: https://example.com/releases/tag/v1.0.0\n";
+        assert_eq!(codes(content), vec!["unreleased-not-first"]);
+    }
+
+    #[test]
+    fn lint_calver() {
+        let content = "# Changelog\n\n\
+            ## [2023.03.1] - 2023-03-15\n\n### Added\n\n- Add a\n\n\
+            ## [2023.03.0+build.2] - 2023-03-10\n\n### Added\n\n- Add b\n\n\
+            ## [2023.03.0+build.1] - 2023-03-01\n\n### Added\n\n- Add c\n\n\
+            [2023.03.1]: https://example.com/compare/v2023.03.0...v2023.03.1\n";
+        let scheme = Scheme::CalVer("YYYY.0M.MICRO".parse().unwrap());
+        let changelog = parse_str(content).unwrap();
+        let today = Date::from_calendar_date(2023, Month::March, 20).unwrap();
+        let messages = lint(&changelog, today, &scheme)
+            .into_iter()
+            .map(|p| p.message)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                "Release 2023.03.0+build.1 is listed more than once",
+                "Release 2023.03.0+build.2 has no [2023.03.0+build.2] reference",
+                "Release 2023.03.0+build.1 has no [2023.03.0+build.1] reference",
+            ]
+        );
+        assert_eq!(codes_with_scheme(content, &scheme)[0], "duplicate-version");
+    }
+
+    #[test]
+    fn parse_error_problem() {
+        let error = parse_str("# Changelog\n\n## Oops\n").unwrap_err();
+        let json = serde_json::to_string(&[Problem::parse_error(&error)]).unwrap();
+        assert_eq!(
+            json,
+            r#"[{"code":"parse-error","message":"CHANGELOG.md:3:4: unexpected content in changelog"}]"#
+        );
+    }
+}
@@ -1,43 +1,385 @@
 #[macro_use]
 extern crate pest_derive;
 
+mod config;
+mod conventional;
 mod diff;
+mod fragments;
 mod generate;
+mod git;
 mod json;
+mod lint;
 mod manifests;
 mod model;
+mod monorepo;
+mod notes;
 mod parser;
+mod remote;
+mod scheme;
+mod transaction;
 
-use crate::diff::{diff_files, FileDiff};
+use crate::config::{parse_preid, Config, VersionFile};
+use crate::conventional::ConventionalCommit;
+use crate::diff::{diff_files, merge_diffs, DiffFormat, FileDiff};
+use crate::fragments::{read_fragments, write_fragment, Fragment};
 use crate::generate::generate_str;
-use crate::json::Json;
-use crate::manifests::detect_manifests;
-use crate::model::{Bump, Change, Release};
+use crate::git::{commit_message, Git};
+use crate::json::{
+    Json, JsonFile, JsonOutcome, JsonPackage, JsonPackages, JsonRelease, SCHEMA_VERSION,
+};
+use crate::lint::{lint, LintFormat, Problem};
+use crate::manifests::{detect_manifests, ManifestType};
+use crate::model::{
+    cmp_precedence, parse_entry_text, parse_version, Change, Changelog, Release, ReleaseSelector,
+    SectionKind,
+};
+use crate::monorepo::{discover_packages, with_dependants, Package};
+use crate::notes::{render_notes, NotesFormat};
 use crate::parser::parse_str;
-use clap::Parser;
+use crate::remote::{infer_compare_urls, CompareUrls, Host};
+use crate::scheme::{CalVerFormat, Scheme, SchemeKind};
+use crate::transaction::write_files;
+use clap::{Parser, Subcommand};
+use semver::{BuildMetadata, Version as SemVer};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::env::{current_dir, set_current_dir};
 use std::fs::{read_to_string, write};
-use std::io::{stderr, stdout, Result, Write};
+use std::io::{stderr, stdout, Error, ErrorKind, Result, Write};
+use std::path::{Path, PathBuf};
+use time::OffsetDateTime;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
+#[command(subcommand_negates_reqs = true)]
 struct Args {
-    #[arg(value_enum)]
-    change: Change,
+    #[command(subcommand)]
+    command: Option<Command>,
 
-    #[arg(long, help = "Whether to force using color")]
+    #[command(flatten)]
+    release: ReleaseArgs,
+
+    #[command(flatten)]
+    config: ConfigArgs,
+
+    #[arg(long, global = true, help = "Whether to force using color")]
     color: bool,
 
-    #[arg(long, help = "Print JSON output")]
+    #[arg(
+        long,
+        global = true,
+        help = "Directory to run in, the project root is searched from there upwards"
+    )]
+    cwd: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+struct ConfigArgs {
+    #[arg(long, global = true, help = "Path of the changelog")]
+    changelog: Option<PathBuf>,
+
+    #[arg(long, global = true, help = "Directory of changelog fragments")]
+    fragments: Option<PathBuf>,
+
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        value_delimiter = ',',
+        help = "Order in which new sections are inserted"
+    )]
+    section_order: Option<Vec<SectionKind>>,
+
+    #[arg(
+        long,
+        global = true,
+        value_parser = parse_preid,
+        help = "Prerelease channel like alpha, beta or rc"
+    )]
+    preid: Option<String>,
+
+    #[arg(long, global = true, help = "Prefix of release tags")]
+    tag_prefix: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        help = "Versioning scheme of releases"
+    )]
+    scheme: Option<SchemeKind>,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Format of CalVer versions like YYYY.0M.MICRO"
+    )]
+    calver_format: Option<CalVerFormat>,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Template of compare URLs using {previous} and {current} tags"
+    )]
+    compare_url: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        help = "Hosting service used for compare URLs, detected from the origin remote by default"
+    )]
+    host: Option<Host>,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Template of the release commit message using {version} and {tag}"
+    )]
+    commit_message: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Include build metadata in the changelog headings and references"
+    )]
+    build_metadata_in_changelog: bool,
+
+    #[arg(
+        long = "manifest",
+        global = true,
+        value_enum,
+        help = "Manifest to update instead of detected ones"
+    )]
+    manifests: Vec<ManifestType>,
+
+    #[arg(long, global = true, help = "Skip updating manifests")]
+    no_manifests: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Python module whose `__version__` should be updated"
+    )]
+    python_version_file: Option<PathBuf>,
+
+    #[arg(
+        long = "version-file",
+        global = true,
+        value_name = "PATH=SEARCH",
+        help = "Files to update the version in, using a {version} placeholder"
+    )]
+    version_files: Vec<VersionFile>,
+}
+
+impl ConfigArgs {
+    /// Applies the arguments to the configuration, resolving paths from `cwd` to `root`.
+    fn apply(self, config: &mut Config, cwd: &Path, root: &Path) {
+        let resolve = |path: PathBuf| {
+            let path = cwd.join(path);
+            match path.strip_prefix(root) {
+                Ok(relative) => relative.to_path_buf(),
+                Err(_) => path,
+            }
+        };
+
+        if let Some(changelog) = self.changelog {
+            config.changelog = resolve(changelog);
+        }
+        if let Some(fragments) = self.fragments {
+            config.fragments = resolve(fragments);
+        }
+        if let Some(section_order) = self.section_order {
+            config.section_order = section_order;
+        }
+        if let Some(preid) = self.preid {
+            config.preid = Some(preid);
+        }
+        if let Some(tag_prefix) = self.tag_prefix {
+            config.tag_prefix = Some(tag_prefix);
+        }
+        if let Some(scheme) = self.scheme {
+            config.scheme = scheme;
+        }
+        if let Some(calver_format) = self.calver_format {
+            config.calver_format = calver_format;
+        }
+        if let Some(compare_url) = self.compare_url {
+            config.compare_url = Some(compare_url);
+        }
+        if let Some(host) = self.host {
+            config.host = Some(host);
+        }
+        if let Some(commit_message) = self.commit_message {
+            config.commit_message = commit_message;
+        }
+        if self.build_metadata_in_changelog {
+            config.build_metadata_in_changelog = true;
+        }
+        if self.no_manifests {
+            config.manifests = Some(Vec::new());
+        } else if !self.manifests.is_empty() {
+            config.manifests = Some(self.manifests);
+        }
+        if let Some(version_file) = self.python_version_file {
+            config.python.version_file = Some(resolve(version_file));
+        }
+        if !self.version_files.is_empty() {
+            config.version_files = self.version_files;
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    #[command(about = "Add an entry to the unreleased section")]
+    Add(AddArgs),
+
+    #[command(about = "Add entries for the Conventional Commits since the last release")]
+    Collect(CollectArgs),
+
+    #[command(about = "Print the release notes of a single release")]
+    Show(ShowArgs),
+
+    #[command(
+        about = "Check the changelog for problems",
+        long_about = "Check the changelog for problems\n\n\
+            Exits with 1 if problems were found and with 2 if the changelog cannot be parsed"
+    )]
+    Lint(LintArgs),
+
+    #[command(about = "Release with the change level inferred from the unreleased section")]
+    Auto(ReleaseOptions),
+
+    #[command(about = "Release an exact version")]
+    Set(SetArgs),
+}
+
+/// How the version of a release is determined.
+enum Target {
+    Change(Change),
+    Auto,
+    Version {
+        version: SemVer,
+        allow_downgrade: bool,
+    },
+}
+
+#[derive(clap::Args, Debug)]
+struct ReleaseArgs {
+    #[arg(value_enum, required = true)]
+    change: Option<Change>,
+
+    #[command(flatten)]
+    options: ReleaseOptions,
+}
+
+#[derive(clap::Args, Debug)]
+struct ReleaseOptions {
+    #[arg(
+        long,
+        help = "Print JSON output, described by the schema in schema/release.schema.json"
+    )]
     json: bool,
 
     #[arg(long, help = "Print all changes to stdout and exit")]
     diff: bool,
 
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        help = "Format of the printed changes, `patch` can be applied with `git apply`"
+    )]
+    format: DiffFormat,
+
     #[arg(long, help = "Create an unreleased section")]
     unreleased: bool,
 
     #[arg(long, help = "Skip modifying the changelog")]
     no_changelog: bool,
+
+    #[arg(
+        long,
+        help = "Build metadata to add to the new version, like sha.abc123"
+    )]
+    build_metadata: Option<BuildMetadata>,
+
+    #[arg(long, help = "Commit the changed files to git")]
+    commit: bool,
+
+    #[arg(
+        long,
+        requires = "commit",
+        help = "Create a git tag for the new version on the release commit"
+    )]
+    tag: bool,
+
+    #[arg(long, help = "Allow committing and tagging with uncommitted changes")]
+    allow_dirty: bool,
+
+    #[arg(
+        long = "package",
+        value_name = "NAME",
+        help = "Package of a monorepo to release, releases all with changes by default"
+    )]
+    packages: Vec<String>,
+}
+
+#[derive(clap::Args, Debug)]
+struct SetArgs {
+    #[arg(value_parser = parse_version, help = "The version to release")]
+    version: SemVer,
+
+    #[arg(long, help = "Allow releasing a version lower than the current one")]
+    allow_downgrade: bool,
+
+    #[command(flatten)]
+    options: ReleaseOptions,
+}
+
+#[derive(clap::Args, Debug)]
+struct AddArgs {
+    #[arg(long = "type", value_enum, help = "The section to add the entry to")]
+    kind: SectionKind,
+
+    #[arg(value_parser = parse_entry_text, help = "The text of the entry")]
+    message: String,
+
+    #[arg(long, help = "Create a fragment instead of editing the changelog")]
+    fragment: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct CollectArgs {
+    #[arg(
+        long = "scope",
+        value_delimiter = ',',
+        help = "Only collect commits with these scopes"
+    )]
+    scopes: Vec<String>,
+
+    #[arg(
+        long = "exclude-scope",
+        value_delimiter = ',',
+        help = "Skip commits with these scopes"
+    )]
+    exclude_scopes: Vec<String>,
+}
+
+#[derive(clap::Args, Debug)]
+struct ShowArgs {
+    #[arg(help = "The version to show, `unreleased` or `latest`")]
+    version: ReleaseSelector,
+
+    #[arg(long, value_enum, default_value_t, help = "The output format")]
+    format: NotesFormat,
+}
+
+#[derive(clap::Args, Debug)]
+struct LintArgs {
+    #[arg(long, value_enum, default_value_t, help = "The output format")]
+    format: LintFormat,
 }
 
 fn main() -> Result<()> {
@@ -47,65 +389,657 @@ fn main() -> Result<()> {
         colored::control::set_override(true);
     }
 
-    let changelog_str = read_to_string("CHANGELOG.md")?;
-    let mut changelog = parse_str(&changelog_str);
+    let cwd = match args.cwd {
+        Some(dir) => current_dir()?.join(dir).canonicalize()?,
+        None => current_dir()?,
+    };
+    let root = Config::find_root(&cwd);
+    set_current_dir(&root)?;
+
+    let mut config = Config::discover(".")?;
+    args.config.apply(&mut config, &cwd, &root);
+    let packages = match &args.command {
+        Some(Command::Auto(options)) => options.packages.as_slice(),
+        Some(Command::Set(set_args)) => set_args.options.packages.as_slice(),
+        None => args.release.options.packages.as_slice(),
+        _ => &[],
+    };
+    if let Err(message) = config.check_tag_prefix(!packages.is_empty()) {
+        eprintln!("{message}");
+        std::process::exit(2);
+    }
+
+    match args.command {
+        Some(Command::Add(add_args)) => add(add_args, &config),
+        Some(Command::Collect(collect_args)) => collect(collect_args, &config),
+        Some(Command::Show(show_args)) => show(show_args, &config),
+        Some(Command::Lint(lint_args)) => check(lint_args, &config),
+        Some(Command::Auto(options)) => release(options, Target::Auto, &config, &root),
+        Some(Command::Set(set_args)) => {
+            let target = Target::Version {
+                version: set_args.version,
+                allow_downgrade: set_args.allow_downgrade,
+            };
+            release(set_args.options, target, &config, &root)
+        }
+        None => {
+            let change = args.release.change.unwrap();
+            release(args.release.options, Target::Change(change), &config, &root)
+        }
+    }
+}
+
+fn read_changelog(config: &Config) -> Result<(String, Changelog)> {
+    let changelog_str = read_to_string(&config.changelog)?;
+    match parse_str(&changelog_str) {
+        Ok(changelog) => Ok((changelog_str, changelog)),
+        Err(error) => {
+            eprintln!(
+                "{}",
+                error.with_path(config.changelog.display().to_string())
+            );
+            std::process::exit(2);
+        }
+    }
+}
+
+fn add(args: AddArgs, config: &Config) -> Result<()> {
+    if args.fragment {
+        let path = write_fragment(&config.fragments, args.kind, &args.message)?;
+        eprintln!("Created fragment {}", path.display());
+        return Ok(());
+    }
+
+    let (_, mut changelog) = read_changelog(config)?;
+    changelog.add_entry(args.kind, args.message, &config.section_order);
+    let new_str = generate_str(&changelog);
+    if let Err(error) = parse_str(&new_str) {
+        let message = format!("the entry would break the changelog: {}", error.message);
+        return Err(Error::new(ErrorKind::InvalidInput, message));
+    }
+    write(&config.changelog, new_str)?;
+    eprintln!("Added entry to {}", args.kind.title());
+
+    Ok(())
+}
+
+fn collect(args: CollectArgs, config: &Config) -> Result<()> {
+    let (_, mut changelog) = read_changelog(config)?;
+    let git = Git::open(".")?;
+
+    let mut filter = config.collect.clone();
+    if !args.scopes.is_empty() {
+        filter.scopes = args.scopes;
+    }
+    if !args.exclude_scopes.is_empty() {
+        filter.exclude_scopes = args.exclude_scopes;
+    }
+
+    let scheme = config.scheme();
+    let tag = match changelog.version() {
+        Some(version) => {
+            let tag = format!("{}{}", config.tag_prefix(), scheme.format(&version));
+            if git.tag_exists(&tag)? {
+                Some(tag)
+            } else {
+                eprintln!("Tag {tag} not found, collecting all commits");
+                None
+            }
+        }
+        None => None,
+    };
+
+    let mut count = 0;
+    for message in git.messages_since(tag.as_deref())? {
+        let Some(commit) = ConventionalCommit::parse(&message) else {
+            continue;
+        };
+        let Some(kind) = commit.section() else {
+            continue;
+        };
+        let text = commit.entry_text();
+        if !filter.includes(commit.scope.as_deref()) || changelog.contains_entry(&text) {
+            continue;
+        }
+        eprintln!("Added entry to {}: {text}", kind.title());
+        changelog.add_entry(kind, text, &config.section_order);
+        count += 1;
+    }
+
+    if count == 0 {
+        eprintln!("No new commits to collect");
+    } else {
+        write(&config.changelog, generate_str(&changelog))?;
+    }
+
+    Ok(())
+}
+
+fn show(args: ShowArgs, config: &Config) -> Result<()> {
+    let (_, changelog) = read_changelog(config)?;
+    let Some(release) = changelog.find(&args.version) else {
+        eprintln!("No release {} found", args.version);
+        std::process::exit(1);
+    };
+    print!("{}", render_notes(release, args.format, &config.scheme()));
+
+    Ok(())
+}
+
+fn check(args: LintArgs, config: &Config) -> Result<()> {
+    let changelog = match args.format {
+        LintFormat::Text => read_changelog(config)?.1,
+        LintFormat::Json => match parse_str(&read_to_string(&config.changelog)?) {
+            Ok(changelog) => changelog,
+            Err(error) => {
+                let error = error.with_path(config.changelog.display().to_string());
+                let problems = [Problem::parse_error(&error)];
+                println!("{}", serde_json::to_string(&problems).unwrap());
+                std::process::exit(2);
+            }
+        },
+    };
+    let today = OffsetDateTime::now_local().unwrap().date();
+    let problems = lint(&changelog, today, &config.scheme());
+
+    match args.format {
+        LintFormat::Text => {
+            for problem in &problems {
+                println!("{problem}");
+            }
+            if !problems.is_empty() {
+                eprintln!("Found {} problem(s)", problems.len());
+            }
+        }
+        LintFormat::Json => println!("{}", serde_json::to_string(&problems).unwrap()),
+    }
+
+    if !problems.is_empty() {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// A changelog to release, with the entries of the fragments merged in.
+struct Pending {
+    changelog_str: String,
+    changelog: Changelog,
+    fragments: Vec<Fragment>,
+}
+
+/// A release computed without changing any files.
+struct Plan {
+    /// The latest version before the release, if there is one.
+    old_version: Option<SemVer>,
+    new_version: SemVer,
+    tag: String,
+    bumped: bool,
+    /// The released section of the changelog and the URL of its reference.
+    notes: Option<JsonRelease>,
+    url: Option<String>,
+    manifests: Vec<ManifestType>,
+    diffs: Vec<FileDiff>,
+}
+
+impl Plan {
+    fn to_json(&self, scheme: &Scheme) -> JsonOutcome {
+        JsonOutcome {
+            bump: self.bumped,
+            version: scheme.format(&self.new_version),
+            previous_version: self.old_version.as_ref().map(|v| scheme.format(v)),
+            tag: self.tag.clone(),
+            date: self.notes.as_ref().and_then(|notes| notes.date.clone()),
+            url: self.url.clone(),
+            notes: self.notes.clone(),
+            manifests: self.manifests.clone(),
+        }
+    }
+}
+
+fn read_pending(args: &ReleaseOptions, config: &Config) -> Result<Pending> {
+    let (changelog_str, mut changelog) = read_changelog(config)?;
 
     if args.unreleased && !changelog.has_unreleased() {
         changelog.releases.insert(0, Release::default());
     }
 
-    let old_version = changelog.version().expect("changelog to have version");
-    let new_version = old_version.bump(args.change);
+    let fragments = if args.no_changelog {
+        Vec::new()
+    } else {
+        read_fragments(&config.fragments)?
+    };
+    for fragment in &fragments {
+        for entry in &fragment.entries {
+            changelog.add_entry(fragment.kind, entry.clone(), &config.section_order);
+        }
+    }
 
-    let mut diffs = Vec::<FileDiff>::new();
-    let bumped = args.no_changelog || changelog.bump(&new_version);
-    if !bumped {
-        if !args.json {
-            eprintln!("No changes to release");
+    Ok(Pending {
+        changelog_str,
+        changelog,
+        fragments,
+    })
+}
+
+/// The previous version, if there is one, the new version, and whether there is anything to
+/// release.
+type Versions = (Option<SemVer>, SemVer, bool);
+
+/// Computes the versions of the release, failing if the target version cannot be released.
+///
+/// Only exact versions can be released without a previous release.
+fn next_version(
+    changelog: &Changelog,
+    target: &Target,
+    config: &Config,
+) -> std::result::Result<Versions, String> {
+    let old_version = changelog.version();
+    let scheme = config.scheme();
+    let bump = |change: Change| {
+        let Some(old_version) = &old_version else {
+            return Err("No previous release found, use `set` to release the first version".into());
+        };
+        let today = OffsetDateTime::now_local().unwrap().date();
+        scheme.bump(old_version, change, config.preid.as_deref(), today)
+    };
+    let (new_version, releasable) = match target {
+        Target::Change(change) => (bump(*change)?, true),
+        Target::Auto => {
+            let change = changelog
+                .find(&ReleaseSelector::Unreleased)
+                .zip(old_version.as_ref())
+                .and_then(|(unreleased, old_version)| unreleased.infer_change(old_version));
+            (bump(change.unwrap_or(Change::Patch))?, change.is_some())
         }
+        Target::Version {
+            version,
+            allow_downgrade,
+        } => {
+            if let Some(old_version) = &old_version {
+                let greater = cmp_precedence(version, old_version) == Ordering::Greater;
+                if !greater && !allow_downgrade {
+                    return Err(format!(
+                        "Version {} is not greater than {}, use --allow-downgrade to release it anyway",
+                        scheme.format(version),
+                        scheme.format(old_version)
+                    ));
+                }
+            }
+            (version.clone(), true)
+        }
+    };
+    Ok((old_version, new_version, releasable))
+}
+
+/// Computes the versions of the release, exiting if the target version cannot be released.
+fn next_version_or_exit(changelog: &Changelog, target: &Target, config: &Config) -> Versions {
+    next_version(changelog, target, config).unwrap_or_else(|error| {
+        eprintln!("{error}");
+        std::process::exit(2);
+    })
+}
+
+/// Bumps the changelog and the manifests in `dir`, building upon the `staged` diffs.
+fn plan_release(
+    args: &ReleaseOptions,
+    pending: Pending,
+    (old_version, new_version, releasable): Versions,
+    config: &Config,
+    dir: &Path,
+    staged: &[FileDiff],
+) -> Result<Plan> {
+    let Pending {
+        changelog_str,
+        mut changelog,
+        fragments,
+    } = pending;
+    let scheme = config.scheme();
+
+    let mut new_version = new_version;
+    if let Some(build_metadata) = &args.build_metadata {
+        new_version.build = build_metadata.clone();
+    }
+    let changelog_version = if config.build_metadata_in_changelog {
+        new_version.clone()
     } else {
+        SemVer {
+            build: BuildMetadata::EMPTY,
+            ..new_version.clone()
+        }
+    };
+    let tag = format!(
+        "{}{}",
+        config.tag_prefix(),
+        scheme.format(&changelog_version)
+    );
+
+    let mut diffs = Vec::<FileDiff>::new();
+    let bumped = args.no_changelog
+        || releasable
+            && changelog.bump(
+                &changelog_version,
+                &scheme,
+                config.tag_prefix(),
+                compare_urls(config).as_ref(),
+            );
+    let mut manifests = Vec::new();
+    if bumped {
         if !args.json {
-            eprintln!("Releasing new version {}", new_version);
+            eprintln!("Releasing new version {}", scheme.format(&new_version));
         }
 
-        let manifest_types = detect_manifests()?;
-        for manifest_type in manifest_types {
+        manifests = manifest_types(config, dir)?;
+        for manifest_type in &manifests {
             if !args.json {
                 eprintln!("Detected {}", manifest_type);
             }
-            let manifest_diffs = manifest_type.change_version(&new_version, !args.diff)?;
-            for diff in manifest_diffs {
-                diffs.push(diff);
-            }
+            let staged = [staged, &diffs].concat();
+            let manifest_diffs = manifest_type.change_version(
+                dir,
+                old_version.as_ref(),
+                &new_version,
+                config,
+                &staged,
+            )?;
+            diffs.extend(manifest_diffs);
         }
     }
 
+    let version_string = scheme.format(&changelog_version);
+    let released = if bumped {
+        changelog.find(&ReleaseSelector::Version(changelog_version))
+    } else {
+        None
+    };
+    let notes = released.map(|release| JsonRelease::new(release, &scheme));
+    let url = released.and_then(|_| {
+        let reference = changelog.refs.iter().find(|r| r.anchor == version_string)?;
+        Some(reference.href.clone())
+    });
+
     let new_str = generate_str(&changelog);
+    diffs.push(FileDiff::new(
+        dir,
+        &config.changelog,
+        changelog_str,
+        new_str,
+    ));
+    if bumped {
+        for fragment in fragments {
+            diffs.push(FileDiff::deletion(dir, fragment.path, fragment.content));
+        }
+    }
 
-    let file_diff = FileDiff::new("CHANGELOG.md", changelog_str, new_str.clone());
-    diffs.push(file_diff);
+    Ok(Plan {
+        old_version,
+        new_version,
+        tag,
+        bumped,
+        notes,
+        url,
+        manifests,
+        diffs,
+    })
+}
 
-    let mut writer: Box<dyn Write> = if args.diff && !args.json {
-        Box::new(stdout())
+fn release(args: ReleaseOptions, target: Target, config: &Config, root: &Path) -> Result<()> {
+    if config.monorepo.is_some() || !args.packages.is_empty() {
+        return release_packages(args, target, config, root);
+    }
+
+    let pending = read_pending(&args, config)?;
+    let versions = next_version_or_exit(&pending.changelog, &target, config);
+    let plan = plan_release(&args, pending, versions, config, root, &[])?;
+    let scheme = config.scheme();
+
+    if !plan.bumped && !args.json {
+        eprintln!("No changes to release");
+    }
+
+    let git = if (args.commit || args.tag) && !args.diff {
+        Some(open_git(&args, &[plan.tag.as_str()])?)
     } else {
-        Box::new(stderr())
+        None
     };
-    diff_files(&mut writer, &diffs)?;
 
-    if bumped && !args.diff {
-        write("CHANGELOG.md", &new_str)?;
+    print_diffs(&args, &plan.diffs)?;
+
+    if plan.bumped && !args.diff {
+        write_files(&plan.diffs)?;
+        if let Some(git) = &git {
+            let message = commit_message(
+                &config.commit_message,
+                &scheme.format(&plan.new_version),
+                &plan.tag,
+            );
+            if args.commit {
+                git.commit(&changed_paths(&plan.diffs), &message)?;
+            }
+            if args.tag {
+                git.tag(&plan.tag, &message)?;
+            }
+        }
         if !args.json {
-            println!("v{}", new_version);
+            println!("{}", plan.tag);
         }
     }
 
     if args.json {
+        let files = if plan.bumped {
+            JsonFile::changed(&plan.diffs, args.format)
+        } else {
+            Vec::new()
+        };
         let json = Json {
-            version: new_version.to_string(),
-            previous_version: old_version.to_string(),
+            schema_version: SCHEMA_VERSION,
+            dry_run: args.diff,
+            release: plan.to_json(&scheme),
+            files,
+        };
+        println!("{}", json);
+    }
+
+    if !plan.bumped {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Releases the packages of a monorepo independently.
+///
+/// Releases the packages selected with `--package`, or all packages with unreleased changes,
+/// and all packages depending on them. Dependants get an entry for each updated dependency
+/// and a patch release, unless their own changes require more.
+fn release_packages(
+    args: ReleaseOptions,
+    target: Target,
+    config: &Config,
+    root: &Path,
+) -> Result<()> {
+    let packages = discover_packages(root, config)?;
+    for name in &args.packages {
+        if !packages.iter().any(|package| package.name == *name) {
+            eprintln!("Package {name} not found");
+            std::process::exit(2);
+        }
+    }
+
+    let mut pendings = Vec::new();
+    for package in &packages {
+        let package_config = config.for_package(&package.name, &package.dir);
+        pendings.push(read_pending(&args, &package_config)?);
+    }
+
+    let mut versions = HashMap::new();
+    for (package, pending) in packages.iter().zip(&pendings) {
+        let selected = if args.packages.is_empty() {
+            let unreleased = pending.changelog.find(&ReleaseSelector::Unreleased);
+            unreleased.is_some_and(|release| release.has_entries())
+        } else {
+            args.packages.contains(&package.name)
+        };
+        if selected {
+            let package_config = config.for_package(&package.name, &package.dir);
+            let (old_version, new_version, releasable) =
+                next_version_or_exit(&pending.changelog, &target, &package_config);
+            if releasable {
+                versions.insert(package.name.clone(), (old_version, new_version));
+            }
+        }
+    }
+    let selected = versions.keys().cloned().collect::<HashSet<_>>();
+    let released = with_dependants(&packages, &selected);
+
+    let mut releases = Vec::new();
+    for (package, pending) in packages.iter().zip(pendings) {
+        if !released.contains(&package.name) {
+            continue;
+        }
+        let package_config = config.for_package(&package.name, &package.dir);
+        let (old_version, new_version) = match versions.remove(&package.name) {
+            Some(versions) => versions,
+            None => {
+                let (old_version, new_version, _) =
+                    next_version_or_exit(&pending.changelog, &Target::Auto, &package_config);
+                (old_version, new_version)
+            }
+        };
+        releases.push((
+            package,
+            package_config,
+            pending,
+            (old_version, new_version, true),
+        ));
+    }
+
+    if releases.is_empty() {
+        if args.json {
+            let json = JsonPackages {
+                schema_version: SCHEMA_VERSION,
+                dry_run: args.diff,
+                bump: false,
+                packages: Vec::new(),
+                files: Vec::new(),
+            };
+            println!("{}", json);
+        } else {
+            eprintln!("No changes to release");
+        }
+        std::process::exit(1);
+    }
+
+    let scheme = config.scheme();
+    let new_versions = releases
+        .iter()
+        .map(|(package, _, _, (_, new_version, _))| (package.name.clone(), new_version.clone()))
+        .collect::<HashMap<_, _>>();
+    let mut diffs = Vec::<FileDiff>::new();
+    let mut plans = Vec::<(&Package, Plan)>::new();
+    for (package, package_config, mut pending, versions) in releases {
+        if !args.json {
+            eprintln!("Releasing package {}", package.name);
+        }
+        let dir = root.join(&package.dir);
+        for dependency in &package.dependencies {
+            let Some(version) = new_versions.get(dependency) else {
+                continue;
+            };
+            if !args.no_changelog {
+                let text = format!("Update `{dependency}` to {}", scheme.format(version));
+                let order = &package_config.section_order;
+                pending
+                    .changelog
+                    .add_entry(SectionKind::Changed, text, order);
+            }
+            for manifest_type in manifest_types(&package_config, &dir)? {
+                let dependency_diffs =
+                    manifest_type.change_dependency_version(&dir, dependency, version, &diffs)?;
+                diffs.extend(dependency_diffs);
+            }
+        }
+
+        let mut plan = plan_release(&args, pending, versions, &package_config, &dir, &diffs)?;
+        diffs.append(&mut plan.diffs);
+        if root.join("Cargo.toml").exists() && root != dir {
+            let workspace_diffs = ManifestType::Cargo.change_dependency_version(
+                root,
+                &package.name,
+                &plan.new_version,
+                &diffs,
+            )?;
+            diffs.extend(workspace_diffs);
+        }
+        plans.push((package, plan));
+    }
+    let diffs = merge_diffs(root, diffs);
+    let bumped = plans.iter().any(|(_, plan)| plan.bumped);
+
+    let tags = plans
+        .iter()
+        .filter(|(_, plan)| plan.bumped)
+        .map(|(_, plan)| plan.tag.as_str())
+        .collect::<Vec<_>>();
+    let git = if (args.commit || args.tag) && !args.diff {
+        Some(open_git(&args, &tags)?)
+    } else {
+        None
+    };
+
+    print_diffs(&args, &diffs)?;
+
+    if bumped && !args.diff {
+        write_files(&diffs)?;
+        if let Some(git) = &git {
+            let released = plans
+                .iter()
+                .filter(|(_, plan)| plan.bumped)
+                .map(|(package, plan)| (package, plan, scheme.format(&plan.new_version)))
+                .collect::<Vec<_>>();
+            if args.commit {
+                let versions = released
+                    .iter()
+                    .map(|(package, _, version)| format!("{} {version}", package.name))
+                    .collect::<Vec<_>>();
+                let message = commit_message(
+                    &config.commit_message,
+                    &versions.join(", "),
+                    &tags.join(", "),
+                );
+                git.commit(&changed_paths(&diffs), &message)?;
+            }
+            if args.tag {
+                for (package, plan, version) in released {
+                    let version = format!("{} {version}", package.name);
+                    let message = commit_message(&config.commit_message, &version, &plan.tag);
+                    git.tag(&plan.tag, &message)?;
+                }
+            }
+        }
+        if !args.json {
+            for tag in &tags {
+                println!("{tag}");
+            }
+        }
+    }
+
+    if args.json {
+        let json = JsonPackages {
+            schema_version: SCHEMA_VERSION,
+            dry_run: args.diff,
             bump: bumped,
+            packages: plans
+                .iter()
+                .map(|(package, plan)| JsonPackage {
+                    name: package.name.clone(),
+                    path: package.dir.display().to_string(),
+                    release: plan.to_json(&scheme),
+                })
+                .collect(),
+            files: JsonFile::changed(&diffs, args.format),
         };
-        println!("{}", json.to_string());
+        println!("{}", json);
     }
 
     if !bumped {
@@ -114,3 +1048,234 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// The manifests to update, configured or detected in `dir`.
+fn manifest_types(config: &Config, dir: &Path) -> Result<Vec<ManifestType>> {
+    match &config.manifests {
+        Some(manifest_types) => Ok(manifest_types.clone()),
+        None => detect_manifests(dir, config),
+    }
+}
+
+/// Prints the diffs to stdout for `--diff`, and to stderr otherwise.
+fn print_diffs(args: &ReleaseOptions, diffs: &[FileDiff]) -> Result<()> {
+    let mut writer: Box<dyn Write> = if args.diff && !args.json {
+        Box::new(stdout())
+    } else {
+        Box::new(stderr())
+    };
+    diff_files(&mut writer, diffs, args.format)
+}
+
+fn changed_paths(diffs: &[FileDiff]) -> Vec<&Path> {
+    diffs
+        .iter()
+        .filter(|diff| diff.is_changed())
+        .map(|diff| diff.path())
+        .collect()
+}
+
+/// The compare URLs from the configuration or inferred from the `origin` remote.
+///
+/// A configured template only covers comparisons, so the link of the first release is still
+/// inferred from the remote.
+fn compare_urls(config: &Config) -> Option<CompareUrls> {
+    let inferred = Git::open(".").ok().and_then(|git| {
+        let remote = git.remote_url("origin")?;
+        infer_compare_urls(&remote, config.host, &git.default_branch("origin"))
+    });
+    match &config.compare_url {
+        Some(template) => Some(CompareUrls {
+            release: template.clone(),
+            unreleased: template.clone(),
+            tag: inferred.and_then(|urls| urls.tag),
+        }),
+        None => inferred,
+    }
+}
+
+/// Opens the git repository and checks that the release can be committed and tagged.
+fn open_git(args: &ReleaseOptions, new_tags: &[&str]) -> Result<Git> {
+    let git = Git::open(".")?;
+    if !args.allow_dirty && git.is_dirty()? {
+        eprintln!("The working tree has uncommitted changes, use --allow-dirty to release anyway");
+        std::process::exit(2);
+    }
+    for new_tag in new_tags {
+        if args.tag && git.tag_exists(new_tag)? {
+            eprintln!("Tag {new_tag} already exists");
+            std::process::exit(2);
+        }
+    }
+    Ok(git)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn global_options_before_command() {
+        let args = Args::try_parse_from(["protokollant", "--color", "add", "--type", "fixed", "x"])
+            .unwrap();
+        assert!(args.color);
+        assert!(matches!(args.command, Some(Command::Add(_))));
+
+        let args = Args::try_parse_from(["protokollant", "--changelog", "X", "lint"]).unwrap();
+        assert_eq!(args.config.changelog, Some(PathBuf::from("X")));
+        assert!(matches!(args.command, Some(Command::Lint(_))));
+
+        let args = Args::try_parse_from(["protokollant", "--cwd", "..", "show", "latest"]).unwrap();
+        assert_eq!(args.cwd, Some(PathBuf::from("..")));
+        assert!(matches!(args.command, Some(Command::Show(_))));
+
+        let args = Args::try_parse_from(["protokollant", "auto", "--commit", "--tag"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Auto(ReleaseOptions { tag: true, .. }))
+        ));
+        assert!(Args::try_parse_from(["protokollant", "minor", "--tag"]).is_err());
+
+        let args = Args::try_parse_from(["protokollant", "--color", "minor"]).unwrap();
+        assert!(args.command.is_none());
+        assert!(matches!(args.release.change, Some(Change::Minor)));
+    }
+
+    fn changelog(content: &str) -> Changelog {
+        parse_str(content).unwrap()
+    }
+
+    fn set(version: &str, allow_downgrade: bool) -> Target {
+        Target::Version {
+            version: SemVer::parse(version).unwrap(),
+            allow_downgrade,
+        }
+    }
+
+    #[test]
+    fn set_version() {
+        let config = Config::default();
+        let released = changelog("# Changelog\n\n## [Unreleased]\n\n## [1.2.3] - 2023-03-15\n\n");
+        let version = |target: &Target| {
+            next_version(&released, target, &config).map(|(_, new_version, _)| new_version)
+        };
+
+        let (old_version, new_version, releasable) =
+            next_version(&released, &set("1.3.0", false), &config).unwrap();
+        assert_eq!(old_version, Some(SemVer::new(1, 2, 3)));
+        assert_eq!(new_version, SemVer::new(1, 3, 0));
+        assert!(releasable);
+
+        assert!(version(&set("1.2.3", false)).is_err());
+        assert!(version(&set("1.2.3+build.7", false)).is_err());
+        assert!(version(&set("1.2.3-rc.1", false)).is_err());
+        assert!(version(&set("1.2.4-rc.1", false)).is_ok());
+        assert_eq!(version(&set("1.0.0", true)).unwrap(), SemVer::new(1, 0, 0));
+
+        let unreleased = changelog("# Changelog\n\n## [Unreleased]\n\n### Added\n\n- Init\n");
+        let (old_version, new_version, _) =
+            next_version(&unreleased, &set("0.1.0", false), &config).unwrap();
+        assert_eq!(old_version, None);
+        assert_eq!(new_version, SemVer::new(0, 1, 0));
+        assert!(next_version(&unreleased, &Target::Auto, &config).is_err());
+        assert!(next_version(&unreleased, &Target::Change(Change::Minor), &config).is_err());
+    }
+
+    fn options(build_metadata: &str) -> ReleaseOptions {
+        ReleaseOptions {
+            json: true,
+            diff: true,
+            format: DiffFormat::default(),
+            unreleased: false,
+            no_changelog: false,
+            build_metadata: Some(build_metadata.parse().unwrap()),
+            commit: false,
+            tag: false,
+            allow_dirty: false,
+            packages: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn plan_build_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path().join("VERSION"), "1.2.3\n").unwrap();
+        let mut config = Config {
+            compare_url: Some("https://example.com/compare/{previous}...{current}".into()),
+            manifests: Some(vec![ManifestType::Files]),
+            version_files: vec!["VERSION={version}".parse().unwrap()],
+            ..Config::default()
+        };
+        let release = |config: &Config| {
+            let content = "# Changelog\n\n## [Unreleased]\n\n### Fixed\n\n- Fix a\n\n\
+                ## [1.2.3] - 2023-03-15\n\n";
+            let pending = Pending {
+                changelog_str: content.into(),
+                changelog: changelog(content),
+                fragments: Vec::new(),
+            };
+            let versions = (Some(SemVer::new(1, 2, 3)), SemVer::new(1, 2, 4), true);
+            plan_release(
+                &options("sha.abc"),
+                pending,
+                versions,
+                config,
+                dir.path(),
+                &[],
+            )
+            .unwrap()
+        };
+        let file = |plan: &Plan, name: &str| {
+            let diff = plan.diffs.iter().find(|d| d.filename() == name).unwrap();
+            diff.right().to_string()
+        };
+
+        let plan = release(&config);
+        assert_eq!(file(&plan, "VERSION"), "1.2.4+sha.abc\n");
+        assert!(file(&plan, "CHANGELOG.md").contains("## [1.2.4] - "));
+        assert_eq!(plan.tag, "v1.2.4");
+        let json = plan.to_json(&Scheme::SemVer);
+        assert_eq!(json.version, "1.2.4+sha.abc");
+        assert_eq!(json.tag, "v1.2.4");
+        assert_eq!(json.notes.unwrap().version, "1.2.4");
+        assert_eq!(
+            json.url.as_deref(),
+            Some("https://example.com/compare/v1.2.3...v1.2.4")
+        );
+
+        config.build_metadata_in_changelog = true;
+        let plan = release(&config);
+        assert_eq!(file(&plan, "VERSION"), "1.2.4+sha.abc\n");
+        assert!(file(&plan, "CHANGELOG.md").contains("## [1.2.4+sha.abc] - "));
+        assert_eq!(plan.tag, "v1.2.4+sha.abc");
+        let json = plan.to_json(&Scheme::SemVer);
+        assert_eq!(json.notes.unwrap().version, "1.2.4+sha.abc");
+        assert_eq!(
+            json.url.as_deref(),
+            Some("https://example.com/compare/v1.2.3...v1.2.4+sha.abc")
+        );
+    }
+
+    #[test]
+    fn add_rejects_invalid_messages() {
+        for message in ["", " ", "a\tb", "a\nb"] {
+            let args = ["protokollant", "add", "--type", "fixed", message];
+            assert!(Args::try_parse_from(args).is_err(), "accepted {message:?}");
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let content = "# Changelog\n\n## [Unreleased]\n\n## [1.0.0] - 2023-03-15\n\n";
+        let config = Config {
+            changelog: dir.path().join("CHANGELOG.md"),
+            ..Config::default()
+        };
+        write(&config.changelog, content).unwrap();
+        let args = AddArgs {
+            kind: SectionKind::Fixed,
+            message: "a\tb".into(),
+            fragment: false,
+        };
+        assert!(add(args, &config).is_err());
+        assert_eq!(read_to_string(&config.changelog).unwrap(), content);
+    }
+}
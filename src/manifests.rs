@@ -1,86 +1,923 @@
+use crate::config::{find_upwards, Config};
 use crate::diff::FileDiff;
+use crate::scheme::Scheme;
+use clap::ValueEnum;
+use regex::{NoExpand, Regex};
 use semver::Version;
+use serde::{Deserialize, Serialize};
+use serde_json::ser::PrettyFormatter;
+use serde_json::Serializer;
+use std::collections::HashSet;
 use std::fmt::{Display, Formatter};
-use std::fs::{read_to_string, write};
-use std::io::Result;
-use std::path::Path;
-use toml_edit::{value, Document};
+use std::fs::read_to_string;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use toml_edit::{Document, Item, TableLike, Value};
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub const CARGO_DEPENDENCY_TABLES: [&str; 3] =
+    ["dependencies", "dev-dependencies", "build-dependencies"];
+pub const NPM_DEPENDENCY_OBJECTS: [&str; 4] = [
+    "dependencies",
+    "devDependencies",
+    "peerDependencies",
+    "optionalDependencies",
+];
+const NPM_LOCKFILES: [&str; 2] = ["package-lock.json", "npm-shrinkwrap.json"];
+const SEMVER_PATTERN: &str =
+    r"(?:0|[1-9]\d*)\.(?:0|[1-9]\d*)\.(?:0|[1-9]\d*)(?:-[0-9A-Za-z.-]+)?(?:\+[0-9A-Za-z.-]+)?";
+/// CalVer versions may have zero-padded components, like `2023.03.0`.
+const CALVER_PATTERN: &str = r"\d+\.\d+\.\d+(?:-[0-9A-Za-z.-]+)?(?:\+[0-9A-Za-z.-]+)?";
+
+#[derive(ValueEnum, Deserialize, Serialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
 pub enum ManifestType {
     Cargo,
+    Npm,
+    Python,
+    Files,
 }
 
 impl ManifestType {
-    pub fn change_version(&self, version: &Version, do_write: bool) -> Result<Vec<FileDiff>> {
+    /// Changes the version in the manifests of this type in `root`.
+    ///
+    /// Files are read from the `staged` diffs if they have been changed already, so that
+    /// the returned diffs build upon them. Version files are searched for `old_version`, or
+    /// for any version if there is no previous release.
+    pub fn change_version(
+        &self,
+        root: &Path,
+        old_version: Option<&Version>,
+        version: &Version,
+        config: &Config,
+        staged: &[FileDiff],
+    ) -> Result<Vec<FileDiff>> {
         match self {
-            ManifestType::Cargo => self.change_cargo_version(version, do_write),
+            ManifestType::Cargo => self.change_cargo_version(root, version, staged),
+            ManifestType::Npm => self.change_npm_version(root, version, staged),
+            ManifestType::Python => self.change_python_version(root, version, config, staged),
+            ManifestType::Files => {
+                self.change_files_version(root, old_version, version, config, staged)
+            }
         }
     }
 
-    fn change_cargo_version(&self, version: &Version, do_write: bool) -> Result<Vec<FileDiff>> {
-        let (toml, name) = self.change_cargo_toml_version(version, do_write)?;
-        let lock = self.change_cargo_lock_version(&name, version, do_write)?;
-        Ok(vec![lock, toml])
+    /// Changes the version requirements on the package `name` in the manifests in `root`.
+    pub fn change_dependency_version(
+        &self,
+        root: &Path,
+        name: &str,
+        version: &Version,
+        staged: &[FileDiff],
+    ) -> Result<Vec<FileDiff>> {
+        match self {
+            ManifestType::Cargo => {
+                let path = root.join("Cargo.toml");
+                let old_toml = read_file(staged, &path)?;
+                let mut manifest = parse_toml(&path, &old_toml)?;
+                let names = HashSet::from([name.to_string()]);
+                change_cargo_dependency_versions(&mut manifest, &names, version);
+                let new_toml = manifest.to_string();
+                Ok(vec![FileDiff::new(root, path, old_toml, new_toml)])
+            }
+            ManifestType::Npm => {
+                let path = root.join("package.json");
+                let diff = change_json_file(root, &path, staged, |package| {
+                    for key in NPM_DEPENDENCY_OBJECTS {
+                        let requirement = package
+                            .get_mut(key)
+                            .and_then(|d| d.get_mut(name))
+                            .filter(|r| r.as_str().is_some_and(|r| !r.contains(':')));
+                        if let Some(requirement) = requirement {
+                            let operator = requirement
+                                .as_str()
+                                .unwrap()
+                                .trim_end_matches(|c: char| !"=^~<> ".contains(c))
+                                .to_string();
+                            *requirement = format!("{operator}{version}").into();
+                        }
+                    }
+                })?;
+                Ok(vec![diff])
+            }
+            ManifestType::Python | ManifestType::Files => Ok(Vec::new()),
+        }
     }
 
-    fn change_cargo_toml_version(
+    fn change_cargo_version(
         &self,
+        root: &Path,
         version: &Version,
-        do_write: bool,
-    ) -> Result<(FileDiff, String)> {
-        let old_toml = read_to_string("Cargo.toml")?;
-        let mut manifest = old_toml.parse::<Document>().unwrap();
-        manifest["package"]["version"] = value(version.to_string());
-        let name = manifest["package"]["name"].as_str().unwrap().to_string();
-        let new_toml = manifest.to_string();
-        if do_write {
-            write("Cargo.toml", &new_toml)?;
+        staged: &[FileDiff],
+    ) -> Result<Vec<FileDiff>> {
+        let root_path = root.join("Cargo.toml");
+        let root_toml = read_file(staged, &root_path)?;
+        let root_manifest = parse_toml(&root_path, &root_toml)?;
+
+        let mut manifests = Vec::new();
+        for member in cargo_workspace_members(root, &root_manifest)? {
+            let path = member.join("Cargo.toml");
+            let toml = read_file(staged, &path)?;
+            let manifest = parse_toml(&path, &toml)?;
+            manifests.push((path, toml, manifest));
         }
+        manifests.insert(0, (root_path, root_toml, root_manifest));
+
+        let names = manifests
+            .iter()
+            .filter_map(|(_, _, manifest)| manifest.get("package")?.get("name")?.as_str())
+            .map(String::from)
+            .collect::<HashSet<_>>();
 
-        let diff = FileDiff::new("Cargo.toml", old_toml, new_toml);
-        Ok((diff, name))
+        let mut diffs = vec![self.change_cargo_lock_version(root, &names, version, staged)?];
+        for (path, old_toml, mut manifest) in manifests {
+            change_cargo_toml_version(&mut manifest, &names, version);
+            let new_toml = manifest.to_string();
+
+            diffs.push(FileDiff::new(root, path, old_toml, new_toml));
+        }
+        Ok(diffs)
     }
 
     fn change_cargo_lock_version(
         &self,
-        name: &str,
+        root: &Path,
+        names: &HashSet<String>,
         version: &Version,
-        do_write: bool,
+        staged: &[FileDiff],
     ) -> Result<FileDiff> {
-        let old_toml = read_to_string("Cargo.lock")?;
-        let mut manifest = old_toml.parse::<Document>().unwrap();
-        let packages = manifest["package"].as_array_of_tables_mut().unwrap();
-        for package in packages.iter_mut() {
-            if package["name"].as_str() == Some(name) {
-                package["version"] = value(version.to_string());
-                break;
+        let path = find_upwards(root, "Cargo.lock").unwrap_or_else(|| root.join("Cargo.lock"));
+        let old_toml = read_file(staged, &path)?;
+        let mut manifest = parse_toml(&path, &old_toml)?;
+        if let Some(packages) = manifest
+            .get_mut("package")
+            .and_then(|p| p.as_array_of_tables_mut())
+        {
+            for package in packages.iter_mut() {
+                let is_member = package
+                    .get("name")
+                    .and_then(|n| n.as_str())
+                    .is_some_and(|n| names.contains(n));
+                if is_member && !package.contains_key("source") {
+                    set_str(&mut package["version"], &version.to_string());
+                }
             }
         }
         let new_toml = manifest.to_string();
-        if do_write {
-            write("Cargo.lock", &new_toml)?;
-        }
 
-        let diff = FileDiff::new("Cargo.lock", old_toml, new_toml);
+        let diff = FileDiff::new(root, path, old_toml, new_toml);
         Ok(diff)
     }
+
+    fn change_npm_version(
+        &self,
+        root: &Path,
+        version: &Version,
+        staged: &[FileDiff],
+    ) -> Result<Vec<FileDiff>> {
+        let version = serde_json::Value::from(version.to_string());
+        let mut diffs = Vec::new();
+
+        let path = root.join("package.json");
+        diffs.push(change_json_file(root, &path, staged, |package| {
+            package["version"] = version.clone();
+        })?);
+
+        for lockfile in NPM_LOCKFILES {
+            let path = root.join(lockfile);
+            if !path.exists() {
+                continue;
+            }
+            diffs.push(change_json_file(root, &path, staged, |lock| {
+                lock["version"] = version.clone();
+                if let Some(package) = lock.pointer_mut("/packages/") {
+                    package["version"] = version.clone();
+                }
+            })?);
+        }
+        Ok(diffs)
+    }
+
+    fn change_python_version(
+        &self,
+        root: &Path,
+        version: &Version,
+        config: &Config,
+        staged: &[FileDiff],
+    ) -> Result<Vec<FileDiff>> {
+        let version = pep440(version);
+        let mut diffs = Vec::new();
+
+        let path = root.join("pyproject.toml");
+        let old_toml = read_file(staged, &path)?;
+        let mut manifest = parse_toml(&path, &old_toml)?;
+        let project_version = manifest
+            .get_mut("project")
+            .and_then(|p| p.get_mut("version"))
+            .filter(|v| v.is_str());
+        if let Some(item) = project_version {
+            set_str(item, &version);
+        } else if let Some(item) = manifest
+            .get_mut("tool")
+            .and_then(|t| t.get_mut("poetry"))
+            .and_then(|p| p.get_mut("version"))
+        {
+            set_str(item, &version);
+        } else if !(is_dynamic_version(&manifest) && config.python.version_file.is_some()) {
+            let message = format!(
+                "no `project.version` or `tool.poetry.version` found in {}",
+                path.display()
+            );
+            return Err(Error::new(ErrorKind::NotFound, message));
+        }
+        let new_toml = manifest.to_string();
+        diffs.push(FileDiff::new(root, path, old_toml, new_toml));
+
+        if let Some(version_file) = &config.python.version_file {
+            let path = root.join(version_file);
+            let old_source = read_file(staged, &path)?;
+            let Some(new_source) = change_python_dunder_version(&old_source, &version) else {
+                let message = format!("no `__version__` assignment found in {}", path.display());
+                return Err(Error::new(ErrorKind::NotFound, message));
+            };
+            diffs.push(FileDiff::new(root, path, old_source, new_source));
+        }
+        Ok(diffs)
+    }
+
+    fn change_files_version(
+        &self,
+        root: &Path,
+        old_version: Option<&Version>,
+        version: &Version,
+        config: &Config,
+        staged: &[FileDiff],
+    ) -> Result<Vec<FileDiff>> {
+        let scheme = config.scheme();
+        let version_pattern = match (old_version, &scheme) {
+            (Some(old_version), _) => regex::escape(&scheme.format(old_version)),
+            (None, Scheme::SemVer) => SEMVER_PATTERN.to_string(),
+            (None, Scheme::CalVer(_)) => CALVER_PATTERN.to_string(),
+        };
+        let version = scheme.format(version);
+
+        let mut files = Vec::<(PathBuf, String, String)>::new();
+        for version_file in &config.version_files {
+            let Some((before, after)) = version_file.search.split_once("{version}") else {
+                let message = format!("`{}` has no {{version}} placeholder", version_file.search);
+                return Err(Error::new(ErrorKind::InvalidInput, message));
+            };
+            let pattern = format!(
+                "{}{version_pattern}{}",
+                regex::escape(before),
+                regex::escape(after)
+            );
+            let regex = Regex::new(&pattern).map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+            let replacement = format!("{before}{version}{after}");
+
+            let mut matched = false;
+            let pattern = escaped_root(root).join(&version_file.path);
+            let paths = glob::glob(&pattern.to_string_lossy())
+                .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+            for path in paths {
+                let path = path.map_err(Error::from)?;
+                let index = match files.iter().position(|(p, _, _)| *p == path) {
+                    Some(index) => index,
+                    None => {
+                        let content = read_file(staged, &path)?;
+                        files.push((path, content.clone(), content));
+                        files.len() - 1
+                    }
+                };
+
+                let content = &mut files[index].2;
+                if regex.is_match(content) {
+                    matched = true;
+                    *content = regex
+                        .replace_all(content, NoExpand(&replacement))
+                        .into_owned();
+                }
+            }
+
+            if !matched {
+                let message = format!(
+                    "`{}` does not match any file in `{}`",
+                    version_file.search, version_file.path
+                );
+                return Err(Error::new(ErrorKind::NotFound, message));
+            }
+        }
+
+        let mut diffs = Vec::new();
+        for (path, old_content, new_content) in files {
+            diffs.push(FileDiff::new(root, path, old_content, new_content));
+        }
+        Ok(diffs)
+    }
 }
 
 impl Display for ManifestType {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             ManifestType::Cargo => f.write_str("Rust (Cargo.lock, Cargo.toml)"),
+            ManifestType::Npm => f.write_str("JavaScript (package.json, package-lock.json)"),
+            ManifestType::Python => f.write_str("Python (pyproject.toml)"),
+            ManifestType::Files => f.write_str("Version files"),
         }
     }
 }
 
-pub fn detect_manifests() -> Result<Vec<ManifestType>> {
+pub fn detect_manifests(root: &Path, config: &Config) -> Result<Vec<ManifestType>> {
     let mut manifests = Vec::new();
 
-    if Path::new("Cargo.toml").exists() {
+    if root.join("Cargo.toml").exists() {
         manifests.push(ManifestType::Cargo);
     }
+    if root.join("package.json").exists() {
+        manifests.push(ManifestType::Npm);
+    }
+    if root.join("pyproject.toml").exists() {
+        manifests.push(ManifestType::Python);
+    }
+    if !config.version_files.is_empty() {
+        manifests.push(ManifestType::Files);
+    }
 
     Ok(manifests)
 }
+
+/// Resolves the `[workspace] members` globs of a Cargo manifest to member directories,
+/// leaving out excluded members and the workspace root itself.
+pub fn cargo_workspace_members(root: &Path, manifest: &Document) -> Result<Vec<PathBuf>> {
+    let Some(workspace) = manifest.get("workspace") else {
+        return Ok(Vec::new());
+    };
+    let excludes = string_array(workspace.get("exclude"))
+        .map(PathBuf::from)
+        .collect::<Vec<_>>();
+
+    let mut members = Vec::new();
+    for pattern in string_array(workspace.get("members")) {
+        let pattern = escaped_root(root).join(pattern);
+        let paths = glob::glob(&pattern.to_string_lossy())
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        for path in paths {
+            let path = path.map_err(Error::from)?;
+            let relative = path.strip_prefix(root).unwrap_or(&path);
+            if relative == Path::new("")
+                || excludes.iter().any(|e| e == relative)
+                || !path.join("Cargo.toml").exists()
+                || members.contains(&path)
+            {
+                continue;
+            }
+            members.push(path);
+        }
+    }
+    Ok(members)
+}
+
+/// Escapes the glob metacharacters in `root`, so that patterns can be joined to it.
+pub fn escaped_root(root: &Path) -> PathBuf {
+    PathBuf::from(glob::Pattern::escape(&root.to_string_lossy()))
+}
+
+fn change_cargo_toml_version(manifest: &mut Document, names: &HashSet<String>, version: &Version) {
+    let version_string = version.to_string();
+    if let Some(item) = manifest
+        .get_mut("workspace")
+        .and_then(|w| w.get_mut("package"))
+        .and_then(|p| p.get_mut("version"))
+    {
+        set_str(item, &version_string);
+    }
+    if let Some(item) = manifest
+        .get_mut("package")
+        .and_then(|p| p.get_mut("version"))
+    {
+        set_str(item, &version_string);
+    }
+    change_cargo_dependency_versions(manifest, names, version);
+}
+
+/// Updates the version requirements on the given packages in all dependency tables.
+fn change_cargo_dependency_versions(
+    manifest: &mut Document,
+    names: &HashSet<String>,
+    version: &Version,
+) {
+    if let Some(dependencies) = manifest
+        .get_mut("workspace")
+        .and_then(|w| w.get_mut("dependencies"))
+        .and_then(|d| d.as_table_like_mut())
+    {
+        change_dependency_versions(dependencies, names, version);
+    }
+    for key in CARGO_DEPENDENCY_TABLES {
+        if let Some(dependencies) = manifest.get_mut(key).and_then(|d| d.as_table_like_mut()) {
+            change_dependency_versions(dependencies, names, version);
+        }
+    }
+    if let Some(targets) = manifest
+        .get_mut("target")
+        .and_then(|t| t.as_table_like_mut())
+    {
+        for (_, target) in targets.iter_mut() {
+            for key in CARGO_DEPENDENCY_TABLES {
+                if let Some(dependencies) = target.get_mut(key).and_then(|d| d.as_table_like_mut())
+                {
+                    change_dependency_versions(dependencies, names, version);
+                }
+            }
+        }
+    }
+}
+
+/// Updates the version requirements of path dependencies on workspace members,
+/// keeping the requirement's operator.
+fn change_dependency_versions(
+    dependencies: &mut dyn TableLike,
+    names: &HashSet<String>,
+    version: &Version,
+) {
+    for (key, dependency) in dependencies.iter_mut() {
+        let Some(dependency) = dependency.as_table_like_mut() else {
+            continue;
+        };
+        let name = dependency
+            .get("package")
+            .and_then(|p| p.as_str())
+            .unwrap_or(key.get());
+        if !names.contains(name) || !dependency.contains_key("path") {
+            continue;
+        }
+        if let Some(item) = dependency.get_mut("version") {
+            let Some(requirement) = item.as_str() else {
+                continue;
+            };
+            let operator = requirement
+                .trim_end_matches(|c: char| !"=^~<> ".contains(c))
+                .to_string();
+            set_str(item, &format!("{operator}{version}"));
+        }
+    }
+}
+
+/// Replaces a string value while keeping its surrounding whitespace and comments.
+fn set_str(item: &mut Item, new_value: &str) {
+    match item.as_value_mut() {
+        Some(value) if value.is_str() => {
+            let decor = value.decor().clone();
+            *value = Value::from(new_value);
+            *value.decor_mut() = decor;
+        }
+        _ => {}
+    }
+}
+
+/// Converts a semantic version to a PEP 440 version, e.g. `1.2.0-rc.1` to `1.2.0rc1`.
+///
+/// Alpha, beta and release candidate tags map to the respective PEP 440 pre-releases,
+/// any other prerelease tag becomes a development release.
+pub fn pep440(version: &Version) -> String {
+    let mut str = format!("{}.{}.{}", version.major, version.minor, version.patch);
+
+    if !version.pre.is_empty() {
+        let identifiers = version.pre.split('.').collect::<Vec<_>>();
+        let number = identifiers
+            .iter()
+            .rev()
+            .find_map(|i| i.parse::<u64>().ok())
+            .unwrap_or(0);
+        let tag = identifiers[0].trim_end_matches(|c: char| c.is_ascii_digit());
+        match tag.to_lowercase().as_str() {
+            "alpha" | "a" => str.push_str(&format!("a{number}")),
+            "beta" | "b" => str.push_str(&format!("b{number}")),
+            "rc" | "c" | "pre" | "preview" => str.push_str(&format!("rc{number}")),
+            _ => str.push_str(&format!(".dev{number}")),
+        }
+    }
+
+    if !version.build.is_empty() {
+        str.push('+');
+        str.push_str(&version.build.replace('-', "."));
+    }
+
+    str
+}
+
+/// Whether a pyproject.toml declares its version as dynamic, e.g. read from a `__version__`.
+fn is_dynamic_version(manifest: &Document) -> bool {
+    string_array(manifest.get("project").and_then(|p| p.get("dynamic"))).any(|d| d == "version")
+}
+
+/// Replaces the string assigned to `__version__` in a Python module.
+fn change_python_dunder_version(source: &str, version: &str) -> Option<String> {
+    let mut lines = source
+        .split_inclusive('\n')
+        .map(String::from)
+        .collect::<Vec<_>>();
+    for line in lines.iter_mut() {
+        let Some(rest) = line.trim_start().strip_prefix("__version__") else {
+            continue;
+        };
+        if !rest.trim_start().starts_with('=') {
+            continue;
+        }
+        let Some(start) = line.find(['"', '\'']) else {
+            continue;
+        };
+        let quote = &line[start..=start];
+        let Some(end) = line[start + 1..].find(quote) else {
+            continue;
+        };
+        line.replace_range(start + 1..start + 1 + end, version);
+        return Some(lines.concat());
+    }
+    None
+}
+
+/// Rewrites a JSON file, keeping the order of keys, the indentation and the final newline.
+fn change_json_file(
+    root: &Path,
+    path: &Path,
+    staged: &[FileDiff],
+    change: impl FnOnce(&mut serde_json::Value),
+) -> Result<FileDiff> {
+    let old_json = read_file(staged, path)?;
+    let mut json = serde_json::from_str::<serde_json::Value>(&old_json).map_err(|e| {
+        let message = format!("failed to parse {}: {e}", path.display());
+        Error::new(ErrorKind::InvalidData, message)
+    })?;
+    change(&mut json);
+
+    let indent = old_json
+        .lines()
+        .nth(1)
+        .map(|line| &line[..line.len() - line.trim_start().len()])
+        .filter(|indent| !indent.is_empty())
+        .unwrap_or("  ");
+    let mut new_json = Vec::new();
+    let formatter = PrettyFormatter::with_indent(indent.as_bytes());
+    serde::Serialize::serialize(
+        &json,
+        &mut Serializer::with_formatter(&mut new_json, formatter),
+    )
+    .map_err(Error::from)?;
+    let mut new_json = String::from_utf8(new_json).unwrap();
+    if old_json.ends_with('\n') {
+        new_json.push('\n');
+    }
+
+    Ok(FileDiff::new(root, path, old_json, new_json))
+}
+
+fn string_array(item: Option<&Item>) -> impl Iterator<Item = &str> {
+    item.and_then(|i| i.as_array())
+        .into_iter()
+        .flat_map(|a| a.iter())
+        .filter_map(|v| v.as_str())
+}
+
+/// Reads a file, taking its content from the last staged diff of it if there is one.
+pub fn read_file(staged: &[FileDiff], path: &Path) -> Result<String> {
+    if let Some(diff) = staged.iter().rev().find(|diff| diff.path() == path) {
+        return Ok(diff.right().to_string());
+    }
+    read_to_string(path).map_err(|e| {
+        let message = format!("failed to read {}: {e}", path.display());
+        Error::new(e.kind(), message)
+    })
+}
+
+pub fn parse_toml(path: &Path, content: &str) -> Result<Document> {
+    content.parse::<Document>().map_err(|e| {
+        let message = format!("failed to parse {}: {e}", path.display());
+        Error::new(ErrorKind::InvalidData, message)
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::diff::merge_diffs;
+    use crate::scheme::SchemeKind;
+    use crate::transaction::write_files;
+    use std::fs::{create_dir_all, write};
+
+    /// Writes a file below `root`, creating its parent directories.
+    pub(crate) fn write_file(root: &Path, path: &str, content: &str) {
+        let path = root.join(path);
+        create_dir_all(path.parent().unwrap()).unwrap();
+        write(path, content).unwrap();
+    }
+
+    #[test]
+    fn change_cargo_workspace_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write_file(
+            root,
+            "Cargo.toml",
+            "[workspace]\nmembers = [\"crates/*\"]\nexclude = [\"crates/c\"]\n\n\
+            [workspace.package]\nversion = \"1.0.0\" # shared\n\n\
+            [workspace.dependencies]\na = { path = \"crates/a\", version = \"=1.0.0\" }\n",
+        );
+        write_file(
+            root,
+            "crates/a/Cargo.toml",
+            "[package]\nname = \"a\"\nversion.workspace = true\n",
+        );
+        write_file(
+            root,
+            "crates/b/Cargo.toml",
+            "[package]\nname = \"b\"\nversion = \"1.0.0\"\n\n\
+            [dependencies]\na = { path = \"../a\", version = \"1.0.0\" }\nserde = \"1.0.0\"\n\n\
+            [target.'cfg(unix)'.dev-dependencies.a]\npath = \"../a\"\nversion = \"^1.0.0\"\n",
+        );
+        write_file(
+            root,
+            "crates/c/Cargo.toml",
+            "[package]\nname = \"c\"\nversion = \"1.0.0\"\n",
+        );
+        write_file(
+            root,
+            "Cargo.lock",
+            "version = 3\n\n\
+            [[package]]\nname = \"a\"\nversion = \"1.0.0\"\n\n\
+            [[package]]\nname = \"b\"\nversion = \"1.0.0\"\n\n\
+            [[package]]\nname = \"c\"\nversion = \"1.0.0\"\n\n\
+            [[package]]\nname = \"serde\"\nversion = \"1.0.0\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\n",
+        );
+
+        let version = Version::parse("1.1.0").unwrap();
+        let diffs = ManifestType::Cargo
+            .change_version(root, None, &version, &Config::default(), &[])
+            .unwrap();
+        write_files(&diffs).unwrap();
+        assert_eq!(diffs.len(), 4);
+
+        let read = |path: &str| read_to_string(root.join(path)).unwrap();
+        assert_eq!(
+            read("Cargo.toml"),
+            "[workspace]\nmembers = [\"crates/*\"]\nexclude = [\"crates/c\"]\n\n\
+            [workspace.package]\nversion = \"1.1.0\" # shared\n\n\
+            [workspace.dependencies]\na = { path = \"crates/a\", version = \"=1.1.0\" }\n"
+        );
+        assert_eq!(
+            read("crates/a/Cargo.toml"),
+            "[package]\nname = \"a\"\nversion.workspace = true\n"
+        );
+        assert_eq!(
+            read("crates/b/Cargo.toml"),
+            "[package]\nname = \"b\"\nversion = \"1.1.0\"\n\n\
+            [dependencies]\na = { path = \"../a\", version = \"1.1.0\" }\nserde = \"1.0.0\"\n\n\
+            [target.'cfg(unix)'.dev-dependencies.a]\npath = \"../a\"\nversion = \"^1.1.0\"\n"
+        );
+        assert_eq!(
+            read("crates/c/Cargo.toml"),
+            "[package]\nname = \"c\"\nversion = \"1.0.0\"\n"
+        );
+        assert_eq!(
+            read("Cargo.lock"),
+            "version = 3\n\n\
+            [[package]]\nname = \"a\"\nversion = \"1.1.0\"\n\n\
+            [[package]]\nname = \"b\"\nversion = \"1.1.0\"\n\n\
+            [[package]]\nname = \"c\"\nversion = \"1.0.0\"\n\n\
+            [[package]]\nname = \"serde\"\nversion = \"1.0.0\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\n"
+        );
+    }
+
+    #[test]
+    fn change_dependency_version_on_staged_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let web = root.join("web");
+        write_file(
+            root,
+            "Cargo.toml",
+            "[package]\nname = \"b\"\nversion = \"1.0.0\"\n\n\
+            [dependencies]\na = { path = \"../a\", version = \"~1.0.0\" }\n",
+        );
+        write_file(
+            root,
+            "Cargo.lock",
+            "version = 3\n\n[[package]]\nname = \"b\"\nversion = \"1.0.0\"\n",
+        );
+        write_file(
+            &web,
+            "package.json",
+            "{\n  \"dependencies\": {\n    \"a\": \"^1.0.0\",\n    \"c\": \"workspace:*\"\n  }\n}\n",
+        );
+
+        let version = Version::parse("1.1.0").unwrap();
+        let mut diffs = ManifestType::Cargo
+            .change_dependency_version(root, "a", &version, &[])
+            .unwrap();
+        let staged = diffs.clone();
+        diffs.extend(
+            ManifestType::Cargo
+                .change_version(
+                    root,
+                    None,
+                    &Version::parse("1.0.1").unwrap(),
+                    &Config::default(),
+                    &staged,
+                )
+                .unwrap(),
+        );
+        for name in ["a", "c"] {
+            let npm_diffs = ManifestType::Npm
+                .change_dependency_version(&web, name, &version, &diffs)
+                .unwrap();
+            diffs.extend(npm_diffs);
+        }
+        write_files(&merge_diffs(root, diffs)).unwrap();
+
+        let read = |path: &str| read_to_string(root.join(path)).unwrap();
+        assert_eq!(
+            read("Cargo.toml"),
+            "[package]\nname = \"b\"\nversion = \"1.0.1\"\n\n\
+            [dependencies]\na = { path = \"../a\", version = \"~1.1.0\" }\n"
+        );
+        assert_eq!(
+            read("web/package.json"),
+            "{\n  \"dependencies\": {\n    \"a\": \"^1.1.0\",\n    \"c\": \"workspace:*\"\n  }\n}\n"
+        );
+    }
+
+    #[test]
+    fn change_npm_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write_file(
+            root,
+            "package.json",
+            "{\n    \"name\": \"pkg\",\n    \"version\": \"1.0.0\",\n    \"author\": \"me\"\n}\n",
+        );
+        write_file(
+            root,
+            "package-lock.json",
+            "{\n  \"name\": \"pkg\",\n  \"version\": \"1.0.0\",\n  \"lockfileVersion\": 3,\n  \"packages\": {\n    \"\": {\n      \"name\": \"pkg\",\n      \"version\": \"1.0.0\"\n    },\n    \"node_modules/dep\": {\n      \"version\": \"1.0.0\"\n    }\n  }\n}",
+        );
+
+        assert_eq!(
+            detect_manifests(root, &Config::default()).unwrap(),
+            vec![ManifestType::Npm]
+        );
+        let version = Version::parse("1.1.0-next.0").unwrap();
+        let diffs = ManifestType::Npm
+            .change_version(root, None, &version, &Config::default(), &[])
+            .unwrap();
+        write_files(&diffs).unwrap();
+        assert_eq!(diffs.len(), 2);
+
+        let read = |path: &str| read_to_string(root.join(path)).unwrap();
+        assert_eq!(
+            read("package.json"),
+            "{\n    \"name\": \"pkg\",\n    \"version\": \"1.1.0-next.0\",\n    \"author\": \"me\"\n}\n"
+        );
+        assert_eq!(
+            read("package-lock.json"),
+            "{\n  \"name\": \"pkg\",\n  \"version\": \"1.1.0-next.0\",\n  \"lockfileVersion\": 3,\n  \"packages\": {\n    \"\": {\n      \"name\": \"pkg\",\n      \"version\": \"1.1.0-next.0\"\n    },\n    \"node_modules/dep\": {\n      \"version\": \"1.0.0\"\n    }\n  }\n}"
+        );
+    }
+
+    #[test]
+    fn change_python_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write_file(
+            root,
+            "pyproject.toml",
+            "[tool.poetry]\nname = \"pkg\"\nversion = \"1.0.0\"\n",
+        );
+        write_file(
+            root,
+            "pkg/__init__.py",
+            "\"\"\"Docs.\"\"\"\n\n__version__ = '1.0.0'  # managed\n",
+        );
+
+        let mut config = Config::default();
+        config.python.version_file = Some(PathBuf::from("pkg/__init__.py"));
+        let version = Version::parse("1.1.0-rc.1").unwrap();
+        let diffs = ManifestType::Python
+            .change_version(root, None, &version, &config, &[])
+            .unwrap();
+        write_files(&diffs).unwrap();
+        assert_eq!(diffs.len(), 2);
+
+        let read = |path: &str| read_to_string(root.join(path)).unwrap();
+        assert_eq!(
+            read("pyproject.toml"),
+            "[tool.poetry]\nname = \"pkg\"\nversion = \"1.1.0rc1\"\n"
+        );
+        assert_eq!(
+            read("pkg/__init__.py"),
+            "\"\"\"Docs.\"\"\"\n\n__version__ = '1.1.0rc1'  # managed\n"
+        );
+    }
+
+    #[test]
+    fn change_python_version_without_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write_file(root, "pyproject.toml", "[project]\nname = \"pkg\"\n");
+        write_file(root, "pkg/__init__.py", "__version__ = \"1.0.0\"\n");
+
+        let mut config = Config::default();
+        let version = Version::parse("1.1.0").unwrap();
+        let error = ManifestType::Python
+            .change_version(root, None, &version, &config, &[])
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::NotFound);
+
+        write_file(
+            root,
+            "pyproject.toml",
+            "[project]\nname = \"pkg\"\ndynamic = [\"version\"]\n",
+        );
+        config.python.version_file = Some(PathBuf::from("pkg/__init__.py"));
+        let diffs = ManifestType::Python
+            .change_version(root, None, &version, &config, &[])
+            .unwrap();
+        assert_eq!(diffs[1].right(), "__version__ = \"1.1.0\"\n");
+    }
+
+    #[test]
+    fn convert_to_pep440() {
+        let pep440 = |v: &str| pep440(&Version::parse(v).unwrap());
+        assert_eq!(pep440("1.2.0"), "1.2.0");
+        assert_eq!(pep440("1.2.0-next.0"), "1.2.0.dev0");
+        assert_eq!(pep440("1.2.0-alpha.3"), "1.2.0a3");
+        assert_eq!(pep440("1.2.0-beta"), "1.2.0b0");
+        assert_eq!(pep440("1.2.0-rc.1"), "1.2.0rc1");
+        assert_eq!(pep440("1.2.0+build-5"), "1.2.0+build.5");
+    }
+
+    #[test]
+    fn change_files_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("[checkout]");
+        let root = root.as_path();
+        write_file(
+            root,
+            "README.md",
+            "Install with `pkg@1.0.0`.\nUpgrade from `pkg@0.9.0`.\nRequires `other@1.0.0`.\n",
+        );
+        write_file(root, "docker/Dockerfile", "LABEL version=\"1.0.0\"\n");
+
+        let mut config = Config {
+            version_files: vec![
+                "README.md=pkg@{version}".parse().unwrap(),
+                "*/Dockerfile=version=\"{version}\"".parse().unwrap(),
+            ],
+            ..Config::default()
+        };
+        let old_version = Version::parse("1.0.0").unwrap();
+        let version = Version::parse("1.1.0-next.0").unwrap();
+        let diffs = ManifestType::Files
+            .change_version(root, Some(&old_version), &version, &config, &[])
+            .unwrap();
+        write_files(&diffs).unwrap();
+        assert_eq!(diffs.len(), 2);
+
+        let read = |path: &str| read_to_string(root.join(path)).unwrap();
+        assert_eq!(
+            read("README.md"),
+            "Install with `pkg@1.1.0-next.0`.\nUpgrade from `pkg@0.9.0`.\nRequires `other@1.0.0`.\n"
+        );
+        assert_eq!(
+            read("docker/Dockerfile"),
+            "LABEL version=\"1.1.0-next.0\"\n"
+        );
+
+        config.version_files = vec!["README.md=missing@{version}".parse().unwrap()];
+        let error = ManifestType::Files
+            .change_version(root, Some(&old_version), &version, &config, &[])
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn change_files_calver_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write_file(root, "deploy.yml", "image: app:2026.08.3\n");
+
+        let config = Config {
+            scheme: SchemeKind::Calver,
+            version_files: vec!["deploy.yml=app:{version}".parse().unwrap()],
+            ..Config::default()
+        };
+        let old_version = Version::parse("2026.8.3").unwrap();
+        let version = Version::parse("2026.9.1").unwrap();
+        let diffs = ManifestType::Files
+            .change_version(root, Some(&old_version), &version, &config, &[])
+            .unwrap();
+        write_files(&diffs).unwrap();
+        assert_eq!(
+            read_to_string(root.join("deploy.yml")).unwrap(),
+            "image: app:2026.09.1\n"
+        );
+    }
+}
@@ -1,6 +1,11 @@
+use crate::remote::CompareUrls;
+use crate::scheme::Scheme;
 use clap::ValueEnum;
 use semver::{Prerelease, Version as SemVer};
+use serde::Deserialize;
+use std::cmp::Ordering;
 use std::fmt::{Debug, Display, Formatter};
+use std::str::FromStr;
 use time::{Date, OffsetDateTime};
 
 #[derive(ValueEnum, Debug, Copy, Clone)]
@@ -27,38 +32,116 @@ impl Changelog {
         }
     }
 
-    pub fn bump(&mut self, new_version: &SemVer) -> bool {
-        if let Some(latest_version) = self.version() {
-            if let Some(unreleased) = self.unreleased() {
-                unreleased.version = Version::Released(new_version.clone());
-                unreleased.date = Some(OffsetDateTime::now_local().unwrap().date());
-
-                if let Some(mut old_ref) = self.refs.first_mut() {
-                    let old_version_string = latest_version.to_string();
-                    let version_string = new_version.to_string();
-                    let href = old_ref.href.clone();
-                    old_ref.href = href.replace(&old_version_string, &version_string);
+    pub fn bump(
+        &mut self,
+        new_version: &SemVer,
+        scheme: &Scheme,
+        tag_prefix: &str,
+        compare_urls: Option<&CompareUrls>,
+    ) -> bool {
+        let latest_version = self.version();
+        let Some(unreleased) = self.unreleased() else {
+            return false;
+        };
+
+        let version_string = scheme.format(new_version);
+        let date = OffsetDateTime::now_local().unwrap().date();
+        unreleased.version = Version::Released(new_version.clone());
+        unreleased.date = Some(date);
+        // Keep the formatted version, since the scheme may differ from plain semver
+        let heading = format!("## [{version_string}] - {date}\n\n");
+        unreleased.heading = Some(Source::new(
+            (unreleased.version.clone(), Some(date)),
+            heading,
+        ));
+
+        let old_version_string = latest_version.map(|version| scheme.format(&version));
+        let old_tag = old_version_string
+            .as_ref()
+            .map(|version| format!("{tag_prefix}{version}"));
+        let new_tag = format!("{tag_prefix}{version_string}");
+        if let Some(urls) = compare_urls {
+            self.update_refs(version_string, urls, old_tag.as_deref(), &new_tag);
+        } else if let (Some(old_version_string), Some(old_ref)) =
+            (old_version_string, self.refs.first_mut())
+        {
+            let href = old_ref.href.clone();
+            old_ref.href = href.replace(&old_version_string, &version_string);
+
+            let new_ref = Ref::new(version_string, href.replace("HEAD", &new_tag));
+            self.refs.insert(1, new_ref);
+        }
+        true
+    }
 
-                    let new_ref = Ref::new(
-                        version_string,
-                        href.replace("HEAD", &format!("v{new_version}")),
-                    );
-                    self.refs.insert(1, new_ref);
+    /// Creates the `[unreleased]` reference and the reference of the new release, or repairs
+    /// them if they do not link the expected tags.
+    ///
+    /// An `[unreleased]` reference to the previous tag is moved to the new tag, keeping its
+    /// format. The first release has no previous tag to compare with, so it links its tag.
+    fn update_refs(
+        &mut self,
+        version_string: String,
+        urls: &CompareUrls,
+        old_tag: Option<&str>,
+        new_tag: &str,
+    ) {
+        let unreleased_href = compare_url(&urls.unreleased, new_tag, "HEAD");
+        let unreleased_index = match self
+            .refs
+            .iter()
+            .position(|r| r.anchor.eq_ignore_ascii_case("unreleased"))
+        {
+            Some(index) => {
+                let existing = &mut self.refs[index];
+                match old_tag.filter(|old_tag| existing.href.contains(old_tag)) {
+                    Some(old_tag) => existing.href = existing.href.replace(old_tag, new_tag),
+                    None if existing.href.contains(new_tag) => {}
+                    None => existing.href = unreleased_href,
                 }
-                return true;
+                index
+            }
+            None => {
+                self.refs
+                    .insert(0, Ref::new("unreleased".into(), unreleased_href));
+                0
             }
+        };
+
+        let new_href = match old_tag {
+            Some(old_tag) => compare_url(&urls.release, old_tag, new_tag),
+            None => match &urls.tag {
+                Some(template) => compare_url(template, "", new_tag),
+                None => return,
+            },
+        };
+        let expected_tags = [old_tag, Some(new_tag)];
+        match self.refs.iter_mut().find(|r| r.anchor == version_string) {
+            Some(existing) => {
+                let is_valid = expected_tags
+                    .iter()
+                    .flatten()
+                    .all(|tag| existing.href.contains(tag));
+                if !is_valid {
+                    existing.href = new_href;
+                }
+            }
+            None => self
+                .refs
+                .insert(unreleased_index + 1, Ref::new(version_string, new_href)),
         }
-        false
     }
 
+    /// The latest released version by precedence, preferring the topmost of equal versions.
     pub fn version(&self) -> Option<SemVer> {
         self.releases
             .iter()
+            .rev()
             .filter_map(|r| match &r.version {
                 Version::Unreleased => None,
                 Version::Released(s) => Some(s),
             })
-            .max()
+            .max_by(|a, b| cmp_precedence(a, b))
             .cloned()
     }
 
@@ -73,6 +156,41 @@ impl Changelog {
             .iter_mut()
             .find(|r| matches!(r.version, Version::Unreleased))
     }
+
+    pub fn find(&self, selector: &ReleaseSelector) -> Option<&Release> {
+        match selector {
+            ReleaseSelector::Unreleased => self
+                .releases
+                .iter()
+                .find(|r| r.version == Version::Unreleased),
+            ReleaseSelector::Latest => {
+                let latest = Version::Released(self.version()?);
+                self.releases.iter().find(|r| r.version == latest)
+            }
+            ReleaseSelector::Version(version) => {
+                let version = Version::Released(version.clone());
+                self.releases.iter().find(|r| r.version == version)
+            }
+        }
+    }
+
+    /// Whether any release already lists an entry with the given text.
+    pub fn contains_entry(&self, text: &str) -> bool {
+        self.releases
+            .iter()
+            .flat_map(|release| &release.sections)
+            .flat_map(|section| &section.entries)
+            .any(|entry| entry.text == text)
+    }
+
+    pub fn add_entry(&mut self, kind: SectionKind, text: impl Into<String>, order: &[SectionKind]) {
+        if !self.has_unreleased() {
+            self.releases.insert(0, Release::default());
+        }
+        let unreleased = self.unreleased().unwrap();
+        let section = unreleased.section_or_insert(kind, order);
+        section.entries.push(Entry::new(text));
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Default)]
@@ -80,10 +198,180 @@ pub struct Release {
     pub version: Version,
     pub date: Option<Date>,
     pub intro: String,
-    pub added: Vec<String>,
-    pub removed: Vec<String>,
-    pub fixed: Vec<String>,
-    pub changed: Vec<String>,
+    pub sections: Vec<Section>,
+    pub heading: Option<Source<(Version, Option<Date>)>>,
+}
+
+impl Release {
+    /// Whether any section of this release has entries.
+    pub fn has_entries(&self) -> bool {
+        self.sections
+            .iter()
+            .any(|section| !section.entries.is_empty())
+    }
+
+    /// Infers the change level from the entries of this release, or `None` if it has no entries.
+    ///
+    /// Removed and breaking entries require a major change, added and deprecated entries a
+    /// minor change and all others, including changed entries that are not breaking, a patch.
+    /// Below 1.0.0, breaking changes only require a minor change.
+    pub fn infer_change(&self, current: &SemVer) -> Option<Change> {
+        let change = self
+            .sections
+            .iter()
+            .flat_map(|section| section.entries.iter().map(move |entry| (section, entry)))
+            .map(|(section, entry)| match section.kind() {
+                _ if entry.is_breaking() => Change::Major,
+                Some(SectionKind::Removed) => Change::Major,
+                Some(SectionKind::Added | SectionKind::Deprecated) => Change::Minor,
+                _ => Change::Patch,
+            })
+            .min_by_key(|change| match change {
+                Change::Major => 0,
+                Change::Minor => 1,
+                _ => 2,
+            })?;
+
+        match change {
+            Change::Major if current.major == 0 => Some(Change::Minor),
+            change => Some(change),
+        }
+    }
+
+    /// Returns the section of the given kind, inserting it at its place in `order` if missing.
+    pub fn section_or_insert(&mut self, kind: SectionKind, order: &[SectionKind]) -> &mut Section {
+        if let Some(index) = self.sections.iter().position(|s| s.kind() == Some(kind)) {
+            return &mut self.sections[index];
+        }
+
+        let rank = |kind: SectionKind| order.iter().position(|k| *k == kind).unwrap_or(order.len());
+        let index = self
+            .sections
+            .iter()
+            .position(|s| s.kind().is_some_and(|k| rank(k) > rank(kind)))
+            .unwrap_or(self.sections.len());
+        if let Some(previous) = index.checked_sub(1).and_then(|i| self.sections.get_mut(i)) {
+            if previous.trailer.is_empty() {
+                previous.trailer.push('\n');
+            }
+        }
+        self.sections.insert(index, Section::new(kind.title()));
+        &mut self.sections[index]
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct Section {
+    pub title: String,
+    pub entries: Vec<Entry>,
+    pub trailer: String,
+    pub heading: Option<Source<String>>,
+}
+
+impl Section {
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            entries: Vec::new(),
+            trailer: "\n".into(),
+            heading: None,
+        }
+    }
+
+    pub fn kind(&self) -> Option<SectionKind> {
+        SectionKind::from_title(&self.title)
+    }
+}
+
+#[derive(ValueEnum, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SectionKind {
+    Added,
+    Changed,
+    Deprecated,
+    Removed,
+    Fixed,
+    Security,
+}
+
+impl SectionKind {
+    pub const ALL: [SectionKind; 6] = [
+        SectionKind::Added,
+        SectionKind::Changed,
+        SectionKind::Deprecated,
+        SectionKind::Removed,
+        SectionKind::Fixed,
+        SectionKind::Security,
+    ];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            SectionKind::Added => "Added",
+            SectionKind::Changed => "Changed",
+            SectionKind::Deprecated => "Deprecated",
+            SectionKind::Removed => "Removed",
+            SectionKind::Fixed => "Fixed",
+            SectionKind::Security => "Security",
+        }
+    }
+
+    pub fn from_title(title: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.title().eq_ignore_ascii_case(title.trim()))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct Entry {
+    pub text: String,
+    pub source: Option<Source<String>>,
+}
+
+impl Entry {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            source: None,
+        }
+    }
+
+    /// Whether the entry is flagged as breaking, like `**Breaking:** Drop support for X`,
+    /// `BREAKING CHANGE: Drop X` or `[breaking] Drop X`.
+    pub fn is_breaking(&self) -> bool {
+        let text = self
+            .text
+            .trim_start_matches(['*', '_', '[', '(', '`', ' '])
+            .to_lowercase();
+        let Some(marker) = text.strip_prefix("breaking") else {
+            return false;
+        };
+        let marker = marker.strip_prefix(" change").unwrap_or(marker);
+        marker.starts_with([':', '*', '_', ']', ')', '`'])
+    }
+}
+
+/// The original text of a parsed node, together with the value it was parsed into.
+///
+/// The generator writes the text back verbatim for as long as the node still has that value,
+/// so that only nodes which have actually been changed get reformatted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Source<T> {
+    value: T,
+    text: String,
+}
+
+impl<T: PartialEq> Source<T> {
+    pub fn new(value: T, text: impl Into<String>) -> Self {
+        Self {
+            value,
+            text: text.into(),
+        }
+    }
+
+    pub fn text_for(&self, value: &T) -> Option<&str> {
+        (self.value == *value).then_some(self.text.as_str())
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Default)]
@@ -102,24 +390,111 @@ impl Display for Version {
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReleaseSelector {
+    Unreleased,
+    Latest,
+    Version(SemVer),
+}
+
+impl FromStr for ReleaseSelector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Prerelease identifiers are case-sensitive, so only the keywords ignore case
+        match s.to_lowercase().as_str() {
+            "unreleased" => Ok(ReleaseSelector::Unreleased),
+            "latest" => Ok(ReleaseSelector::Latest),
+            _ => parse_version(s.strip_prefix(['v', 'V']).unwrap_or(s))
+                .map(ReleaseSelector::Version)
+                .map_err(|e| format!("expected a version, `unreleased` or `latest`: {e}")),
+        }
+    }
+}
+
+impl Display for ReleaseSelector {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReleaseSelector::Unreleased => f.write_str("unreleased"),
+            ReleaseSelector::Latest => f.write_str("latest"),
+            ReleaseSelector::Version(v) => Display::fmt(v, f),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Default)]
 pub struct Ref {
     pub anchor: String,
     pub href: String,
+    pub source: Option<Source<(String, String)>>,
 }
 
 impl Ref {
     pub fn new(anchor: String, href: String) -> Self {
-        Self { anchor, href }
+        Self {
+            anchor,
+            href,
+            source: None,
+        }
     }
 }
 
+/// Parses a version, also accepting zero-padded components like in the CalVer version `2023.03.1`.
+pub fn parse_version(text: &str) -> Result<SemVer, semver::Error> {
+    SemVer::parse(text).or_else(|error| {
+        let end = text.find(['-', '+']).unwrap_or(text.len());
+        let (core, rest) = text.split_at(end);
+        let unpadded = core
+            .split('.')
+            .map(|part| match part.trim_start_matches('0') {
+                "" if !part.is_empty() => "0",
+                trimmed => trimmed,
+            })
+            .collect::<Vec<_>>()
+            .join(".");
+        if unpadded == core {
+            return Err(error);
+        }
+        SemVer::parse(&(unpadded + rest)).map_err(|_| error)
+    })
+}
+
+/// Checks that the text of a new entry fits on a single bullet line of the changelog.
+pub fn parse_entry_text(text: &str) -> Result<String, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        Err("the entry must not be empty".into())
+    } else if text.contains(['\n', '\r']) {
+        Err("the entry must be a single line".into())
+    } else if text.chars().any(char::is_control) {
+        Err("the entry must not contain control characters like tabs".into())
+    } else {
+        Ok(text.into())
+    }
+}
+
+/// Compares versions by their precedence, which ignores build metadata.
+pub fn cmp_precedence(a: &SemVer, b: &SemVer) -> Ordering {
+    (a.major, a.minor, a.patch, &a.pre).cmp(&(b.major, b.minor, b.patch, &b.pre))
+}
+
+/// Expands a compare URL template with the `{previous}` and `{current}` tags.
+pub fn compare_url(template: &str, previous: &str, current: &str) -> String {
+    template
+        .replace("{previous}", previous)
+        .replace("{current}", current)
+}
+
 pub trait Bump {
-    fn bump(&self, change: Change) -> Self;
+    /// Bumps the version by the given change level.
+    ///
+    /// New prereleases use the `preid` channel, defaulting to `next`. Existing prereleases
+    /// stay in their channel unless a different `preid` is given, which promotes them to it.
+    fn bump(&self, change: Change, preid: Option<&str>) -> Self;
 }
 
 impl Bump for SemVer {
-    fn bump(&self, change: Change) -> Self {
+    fn bump(&self, change: Change, preid: Option<&str>) -> Self {
         match change {
             Change::Major => {
                 if self.pre.is_empty() || self.minor > 0 || self.patch > 0 {
@@ -145,23 +520,43 @@ impl Bump for SemVer {
             Change::Prerelease => {
                 if self.pre.is_empty() {
                     let mut next = SemVer::new(self.major, self.minor, self.patch + 1);
-                    next.pre = Prerelease::new("next.0").unwrap();
-                    next
-                } else {
-                    let mut next = SemVer::new(self.major, self.minor, self.patch);
-                    let pre = self.pre.as_str();
-                    let mut split = pre.rsplit('.');
-                    let pre_index = split.next().unwrap().parse::<u64>().unwrap() + 1;
-                    let pre_tag = split.next().unwrap();
-
-                    next.pre = Prerelease::new(&format!("{pre_tag}.{pre_index}")).unwrap();
-                    next
+                    next.pre = prerelease(preid.unwrap_or("next"), 0);
+                    return next;
+                }
+
+                let mut next = SemVer::new(self.major, self.minor, self.patch);
+                let channel = self.pre.split('.').next().unwrap();
+                match preid.filter(|preid| *preid != channel) {
+                    Some(preid) => {
+                        next.pre = prerelease(preid, 0);
+                        if next < *self {
+                            next.patch += 1;
+                        }
+                    }
+                    None => {
+                        let mut identifiers =
+                            self.pre.split('.').map(String::from).collect::<Vec<_>>();
+                        let number = identifiers
+                            .iter_mut()
+                            .rev()
+                            .find(|i| i.parse::<u64>().is_ok());
+                        match number {
+                            Some(n) => *n = (n.parse::<u64>().unwrap() + 1).to_string(),
+                            None => identifiers.push("0".into()),
+                        }
+                        next.pre = Prerelease::new(&identifiers.join(".")).unwrap();
+                    }
                 }
+                next
             }
         }
     }
 }
 
+fn prerelease(preid: &str, number: u64) -> Prerelease {
+    Prerelease::new(&format!("{preid}.{number}")).expect("valid prerelease identifier")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -170,75 +565,356 @@ mod tests {
     fn bump_major() {
         let v_act = SemVer::parse("1.2.3").unwrap();
         let v_exp = SemVer::parse("2.0.0").unwrap();
-        assert_eq!(v_act.bump(Change::Major), v_exp);
+        assert_eq!(v_act.bump(Change::Major, None), v_exp);
 
         let v_act = SemVer::parse("0.0.0").unwrap();
         let v_exp = SemVer::parse("1.0.0").unwrap();
-        assert_eq!(v_act.bump(Change::Major), v_exp);
+        assert_eq!(v_act.bump(Change::Major, None), v_exp);
 
         let v_act = SemVer::parse("1.0.0-next.0").unwrap();
         let v_exp = SemVer::parse("1.0.0").unwrap();
-        assert_eq!(v_act.bump(Change::Major), v_exp);
+        assert_eq!(v_act.bump(Change::Major, None), v_exp);
 
         let v_act = SemVer::parse("1.0.1-next.0").unwrap();
         let v_exp = SemVer::parse("2.0.0").unwrap();
-        assert_eq!(v_act.bump(Change::Major), v_exp);
+        assert_eq!(v_act.bump(Change::Major, None), v_exp);
 
         let v_act = SemVer::parse("1.1.0-next.0").unwrap();
         let v_exp = SemVer::parse("2.0.0").unwrap();
-        assert_eq!(v_act.bump(Change::Major), v_exp);
+        assert_eq!(v_act.bump(Change::Major, None), v_exp);
     }
 
     #[test]
     fn bump_minor() {
         let v_act = SemVer::parse("1.2.3").unwrap();
         let v_exp = SemVer::parse("1.3.0").unwrap();
-        assert_eq!(v_act.bump(Change::Minor), v_exp);
+        assert_eq!(v_act.bump(Change::Minor, None), v_exp);
 
         let v_act = SemVer::parse("0.0.0").unwrap();
         let v_exp = SemVer::parse("0.1.0").unwrap();
-        assert_eq!(v_act.bump(Change::Minor), v_exp);
+        assert_eq!(v_act.bump(Change::Minor, None), v_exp);
 
         let v_act = SemVer::parse("1.0.1-next.0").unwrap();
         let v_exp = SemVer::parse("1.1.0").unwrap();
-        assert_eq!(v_act.bump(Change::Minor), v_exp);
+        assert_eq!(v_act.bump(Change::Minor, None), v_exp);
 
         let v_act = SemVer::parse("1.0.0-next.0").unwrap();
         let v_exp = SemVer::parse("1.0.0").unwrap();
-        assert_eq!(v_act.bump(Change::Minor), v_exp);
+        assert_eq!(v_act.bump(Change::Minor, None), v_exp);
 
         let v_act = SemVer::parse("1.1.0-next.0").unwrap();
         let v_exp = SemVer::parse("1.1.0").unwrap();
-        assert_eq!(v_act.bump(Change::Minor), v_exp);
+        assert_eq!(v_act.bump(Change::Minor, None), v_exp);
     }
 
     #[test]
     fn bump_patch() {
         let v_act = SemVer::parse("1.2.3").unwrap();
         let v_exp = SemVer::parse("1.2.4").unwrap();
-        assert_eq!(v_act.bump(Change::Patch), v_exp);
+        assert_eq!(v_act.bump(Change::Patch, None), v_exp);
 
         let v_act = SemVer::parse("0.0.0").unwrap();
         let v_exp = SemVer::parse("0.0.1").unwrap();
-        assert_eq!(v_act.bump(Change::Patch), v_exp);
+        assert_eq!(v_act.bump(Change::Patch, None), v_exp);
 
         let v_act = SemVer::parse("1.0.1-next.0").unwrap();
         let v_exp = SemVer::parse("1.0.1").unwrap();
-        assert_eq!(v_act.bump(Change::Patch), v_exp);
+        assert_eq!(v_act.bump(Change::Patch, None), v_exp);
     }
 
     #[test]
     fn bump_prerelease() {
         let v_act = SemVer::parse("1.2.3").unwrap();
         let v_exp = SemVer::parse("1.2.4-next.0").unwrap();
-        assert_eq!(v_act.bump(Change::Prerelease), v_exp);
+        assert_eq!(v_act.bump(Change::Prerelease, None), v_exp);
 
         let v_act = SemVer::parse("0.0.0").unwrap();
         let v_exp = SemVer::parse("0.0.1-next.0").unwrap();
-        assert_eq!(v_act.bump(Change::Prerelease), v_exp);
+        assert_eq!(v_act.bump(Change::Prerelease, None), v_exp);
 
         let v_act = SemVer::parse("1.0.1-next.0").unwrap();
         let v_exp = SemVer::parse("1.0.1-next.1").unwrap();
-        assert_eq!(v_act.bump(Change::Prerelease), v_exp);
+        assert_eq!(v_act.bump(Change::Prerelease, None), v_exp);
+    }
+
+    #[test]
+    fn bump_prerelease_channels() {
+        let bump = |v: &str, preid: Option<&str>| {
+            SemVer::parse(v)
+                .unwrap()
+                .bump(Change::Prerelease, preid)
+                .to_string()
+        };
+
+        assert_eq!(bump("1.2.3", Some("alpha")), "1.2.4-alpha.0");
+        assert_eq!(bump("1.2.4-alpha.3", None), "1.2.4-alpha.4");
+        assert_eq!(bump("1.2.4-alpha.3", Some("alpha")), "1.2.4-alpha.4");
+        assert_eq!(bump("1.2.4-alpha.3", Some("beta")), "1.2.4-beta.0");
+        assert_eq!(bump("1.2.4-beta.0", Some("rc")), "1.2.4-rc.0");
+        assert_eq!(bump("1.2.4-rc.2", Some("alpha")), "1.2.5-alpha.0");
+    }
+
+    #[test]
+    fn bump_prerelease_formats() {
+        let bump = |v: &str| {
+            SemVer::parse(v)
+                .unwrap()
+                .bump(Change::Prerelease, None)
+                .to_string()
+        };
+
+        assert_eq!(bump("1.0.0-alpha"), "1.0.0-alpha.0");
+        assert_eq!(bump("1.0.0-rc.1.hotfix"), "1.0.0-rc.2.hotfix");
+        assert_eq!(bump("1.0.0-3"), "1.0.0-4");
+        assert_eq!(bump("1.0.0-alpha3"), "1.0.0-alpha3.0");
+        assert_eq!(bump("1.0.0-x.7.z.92"), "1.0.0-x.7.z.93");
+    }
+
+    #[test]
+    fn add_entry() {
+        let mut changelog = crate::parser::parse_str(
+            "# Changelog\n\n## [1.0.0] - 2023-03-15\n\n### Fixed\n\n- Fix a\n",
+        )
+        .unwrap();
+
+        let order = SectionKind::ALL;
+        changelog.add_entry(SectionKind::Fixed, "Fix b", &order);
+        changelog.add_entry(SectionKind::Added, "Add c", &order);
+        changelog.add_entry(SectionKind::Fixed, "Fix d", &order);
+        changelog.add_entry(SectionKind::Security, "Secure e", &order);
+
+        assert_eq!(
+            crate::generate::generate_str(&changelog),
+            "# Changelog\n\n\
+            ## [Unreleased]\n\n\
+            ### Added\n\n- Add c\n\n\
+            ### Fixed\n\n- Fix b\n- Fix d\n\n\
+            ### Security\n\n- Secure e\n\n\
+            ## [1.0.0] - 2023-03-15\n\n### Fixed\n\n- Fix a\n"
+        );
+    }
+
+    #[test]
+    fn bump_with_compare_url() {
+        let mut changelog = crate::parser::parse_str(
+            "# Changelog\n\n## [Unreleased]\n\n### Added\n\n- Add a\n\n\
+            ## [1.0.0] - 2023-03-15\n\n### Added\n\n- Init\n",
+        )
+        .unwrap();
+
+        let template = "https://example.com/compare/{previous}..{current}";
+        let urls = CompareUrls {
+            release: template.into(),
+            unreleased: template.into(),
+            tag: None,
+        };
+        let new_version = SemVer::parse("1.1.0").unwrap();
+        assert!(changelog.bump(&new_version, &Scheme::SemVer, "release-", Some(&urls)));
+
+        let refs = changelog
+            .refs
+            .iter()
+            .map(|r| (r.anchor.as_str(), r.href.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            refs,
+            vec![
+                (
+                    "unreleased",
+                    "https://example.com/compare/release-1.1.0..HEAD"
+                ),
+                (
+                    "1.1.0",
+                    "https://example.com/compare/release-1.0.0..release-1.1.0"
+                ),
+            ]
+        );
+
+        let date = changelog.releases[0].date.unwrap();
+        assert_eq!(
+            crate::generate::generate_str(&changelog),
+            format!(
+                "# Changelog\n\n## [1.1.0] - {date}\n\n### Added\n\n- Add a\n\n\
+                ## [1.0.0] - 2023-03-15\n\n### Added\n\n- Init\n\n\
+                [unreleased]: https://example.com/compare/release-1.1.0..HEAD\n\
+                [1.1.0]: https://example.com/compare/release-1.0.0..release-1.1.0\n"
+            )
+        );
+    }
+
+    #[test]
+    fn bump_first_release() {
+        let mut changelog =
+            crate::parser::parse_str("# Changelog\n\n## [Unreleased]\n\n### Added\n\n- Init\n")
+                .unwrap();
+
+        let new_version = SemVer::parse("0.1.0").unwrap();
+        assert!(changelog.bump(&new_version, &Scheme::SemVer, "v", None));
+        assert_eq!(changelog.version(), Some(new_version));
+        assert!(changelog.refs.is_empty());
+
+        let mut changelog =
+            crate::parser::parse_str("# Changelog\n\n## [Unreleased]\n\n### Added\n\n- Init\n")
+                .unwrap();
+        let urls = CompareUrls {
+            release: "https://example.com/compare/{previous}...{current}".into(),
+            unreleased: "https://example.com/compare/{previous}...{current}".into(),
+            tag: Some("https://example.com/releases/tag/{current}".into()),
+        };
+        let new_version = SemVer::parse("1.0.0").unwrap();
+        assert!(changelog.bump(&new_version, &Scheme::SemVer, "v", Some(&urls)));
+        let refs = changelog
+            .refs
+            .iter()
+            .map(|r| (r.anchor.as_str(), r.href.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            refs,
+            vec![
+                ("unreleased", "https://example.com/compare/v1.0.0...HEAD"),
+                ("1.0.0", "https://example.com/releases/tag/v1.0.0"),
+            ]
+        );
+    }
+
+    #[test]
+    fn bump_keeps_valid_refs() {
+        let mut changelog = crate::parser::parse_str(
+            "# Changelog\n\n## [Unreleased]\n\n### Added\n\n- Add a\n\n\
+            ## [1.0.0] - 2023-03-15\n\n### Added\n\n- Init\n\n\
+            [unreleased]: https://git.example.com/diff?from=v1.0.0&to=HEAD\n\
+            [1.1.0]: https://git.example.com/diff?from=v1.0.0&to=v1.1.0\n\
+            [1.0.0]: https://git.example.com/tag?name=v1.0.0\n",
+        )
+        .unwrap();
+
+        let urls = CompareUrls {
+            release: "https://example.com/compare/{previous}...{current}".into(),
+            unreleased: "https://example.com/compare/{previous}...{current}".into(),
+            tag: None,
+        };
+        let new_version = SemVer::parse("1.1.0").unwrap();
+        assert!(changelog.bump(&new_version, &Scheme::SemVer, "v", Some(&urls)));
+
+        let refs = changelog
+            .refs
+            .iter()
+            .map(|r| (r.anchor.as_str(), r.href.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            refs,
+            vec![
+                (
+                    "unreleased",
+                    "https://git.example.com/diff?from=v1.1.0&to=HEAD"
+                ),
+                (
+                    "1.1.0",
+                    "https://git.example.com/diff?from=v1.0.0&to=v1.1.0"
+                ),
+                ("1.0.0", "https://git.example.com/tag?name=v1.0.0"),
+            ]
+        );
+    }
+
+    #[test]
+    fn version_by_precedence() {
+        let changelog = crate::parser::parse_str(
+            "# Changelog\n\n## [1.0.0+a] - 2023-03-15\n\n## [1.0.0+b] - 2023-03-01\n\n\
+            ## [1.0.0-rc.1] - 2023-02-01\n\n",
+        )
+        .unwrap();
+        assert_eq!(changelog.version(), Some(SemVer::parse("1.0.0+a").unwrap()));
+        assert_eq!(
+            cmp_precedence(
+                &SemVer::parse("1.0.0+a").unwrap(),
+                &SemVer::parse("1.0.0+b").unwrap()
+            ),
+            Ordering::Equal
+        );
+    }
+
+    #[test]
+    fn bump_repairs_refs() {
+        let mut changelog = crate::parser::parse_str(
+            "# Changelog\n\n## [Unreleased]\n\n## [1.0.0] - 2023-03-15\n\n\
+            [1.0.0]: https://old.example.com/v1.0.0\n\
+            [1.1.0]: https://old.example.com/v1.1.0\n\
+            [Unreleased]: https://old.example.com/HEAD\n",
+        )
+        .unwrap();
+
+        let urls = CompareUrls {
+            release: "https://example.com/compare/{previous}...{current}".into(),
+            unreleased: "https://example.com/compare/{previous}...main".into(),
+            tag: None,
+        };
+        let new_version = SemVer::parse("1.1.0").unwrap();
+        assert!(changelog.bump(&new_version, &Scheme::SemVer, "v", Some(&urls)));
+
+        let refs = changelog
+            .refs
+            .iter()
+            .map(|r| (r.anchor.as_str(), r.href.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            refs,
+            vec![
+                ("1.0.0", "https://old.example.com/v1.0.0"),
+                ("1.1.0", "https://example.com/compare/v1.0.0...v1.1.0"),
+                ("Unreleased", "https://example.com/compare/v1.1.0...main"),
+            ]
+        );
+    }
+
+    #[test]
+    fn infer_change() {
+        let infer = |sections: &str, version: &str| {
+            let content = format!("# Changelog\n\n## [Unreleased]\n\n{sections}");
+            let changelog = crate::parser::parse_str(&content).unwrap();
+            let version = SemVer::parse(version).unwrap();
+            changelog.releases[0]
+                .infer_change(&version)
+                .map(|c| format!("{c:?}"))
+        };
+
+        assert_eq!(infer("", "1.0.0"), None);
+        assert_eq!(infer("### Added\n\n", "1.0.0"), None);
+        assert_eq!(
+            infer("### Fixed\n\n- Fix a\n\n### Security\n\n- b\n", "1.0.0"),
+            Some("Patch".into())
+        );
+        assert_eq!(
+            infer("### Deprecated\n\n- a\n\n### Fixed\n\n- b\n", "1.0.0"),
+            Some("Minor".into())
+        );
+        assert_eq!(
+            infer("### Changed\n\n- Change a\n", "1.0.0"),
+            Some("Patch".into())
+        );
+        assert_eq!(
+            infer("### Added\n\n- a\n\n### Removed\n\n- b\n", "1.0.0"),
+            Some("Major".into())
+        );
+        assert_eq!(
+            infer("### Changed\n\n- **Breaking:** c\n", "1.0.0"),
+            Some("Major".into())
+        );
+        assert_eq!(
+            infer("### Changed\n\n- BREAKING CHANGE: c\n", "1.0.0"),
+            Some("Major".into())
+        );
+        assert_eq!(
+            infer("### Fixed\n\n- [breaking] c\n", "1.0.0"),
+            Some("Major".into())
+        );
+        assert_eq!(
+            infer(
+                "### Fixed\n\n- Breaking out of the retry loop no longer hangs\n",
+                "1.2.3"
+            ),
+            Some("Patch".into())
+        );
+        assert_eq!(infer("### Removed\n\n- b\n", "0.5.1"), Some("Minor".into()));
     }
 }
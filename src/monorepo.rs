@@ -0,0 +1,333 @@
+use crate::config::Config;
+use crate::manifests::{
+    cargo_workspace_members, escaped_root, parse_toml, read_file, CARGO_DEPENDENCY_TABLES,
+    NPM_DEPENDENCY_OBJECTS,
+};
+use std::collections::HashSet;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use toml_edit::{Document, Item};
+
+/// An independently versioned package of a monorepo.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Package {
+    pub name: String,
+    /// The directory of the package, relative to the repository root.
+    pub dir: PathBuf,
+    /// The other packages of the monorepo this package depends on.
+    pub dependencies: Vec<String>,
+}
+
+/// Finds the packages of a monorepo, which are the directories with a changelog matching the
+/// configured globs or, if there are none, the members of the Cargo or npm workspace.
+pub fn discover_packages(root: &Path, config: &Config) -> Result<Vec<Package>> {
+    let globs = config
+        .monorepo
+        .as_ref()
+        .map(|monorepo| monorepo.packages.clone())
+        .unwrap_or_default();
+    let dirs = if globs.is_empty() {
+        workspace_members(root)?
+    } else {
+        glob_dirs(root, &globs)?
+    };
+
+    let mut packages = Vec::<Package>::new();
+    for dir in dirs {
+        if !dir.join(&config.changelog).is_file() {
+            continue;
+        }
+        let relative = dir.strip_prefix(root).unwrap_or(&dir).to_path_buf();
+        let (name, dependencies) = read_package(&dir)?;
+        let name = name.unwrap_or_else(|| {
+            let name = dir.file_name().unwrap_or(dir.as_os_str());
+            name.to_string_lossy().into_owned()
+        });
+        if packages.iter().any(|package| package.name == name) {
+            let message = format!("package `{name}` found more than once");
+            return Err(Error::new(ErrorKind::InvalidData, message));
+        }
+        packages.push(Package {
+            name,
+            dir: relative,
+            dependencies,
+        });
+    }
+
+    let names = packages
+        .iter()
+        .map(|package| package.name.clone())
+        .collect::<HashSet<_>>();
+    for package in &mut packages {
+        let name = package.name.clone();
+        package
+            .dependencies
+            .retain(|dependency| names.contains(dependency) && *dependency != name);
+        package.dependencies.sort();
+        package.dependencies.dedup();
+    }
+    Ok(packages)
+}
+
+/// Returns the given packages and all packages depending on them, directly or transitively.
+pub fn with_dependants(packages: &[Package], names: &HashSet<String>) -> HashSet<String> {
+    let mut released = names.clone();
+    loop {
+        let dependants = packages
+            .iter()
+            .filter(|package| !released.contains(&package.name))
+            .filter(|package| package.dependencies.iter().any(|d| released.contains(d)))
+            .map(|package| package.name.clone())
+            .collect::<Vec<_>>();
+        if dependants.is_empty() {
+            return released;
+        }
+        released.extend(dependants);
+    }
+}
+
+fn workspace_members(root: &Path) -> Result<Vec<PathBuf>> {
+    let mut members = Vec::new();
+
+    let cargo_path = root.join("Cargo.toml");
+    if cargo_path.exists() {
+        let manifest = parse_toml(&cargo_path, &read_file(&[], &cargo_path)?)?;
+        members.extend(cargo_workspace_members(root, &manifest)?);
+    }
+
+    let npm_path = root.join("package.json");
+    if npm_path.exists() {
+        let package = parse_json(&npm_path)?;
+        let workspaces = package
+            .get("workspaces")
+            .map(|w| w.get("packages").unwrap_or(w))
+            .and_then(|w| w.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|w| w.as_str().map(String::from))
+            .collect::<Vec<_>>();
+        for dir in glob_dirs(root, &workspaces)? {
+            if !members.contains(&dir) {
+                members.push(dir);
+            }
+        }
+    }
+
+    Ok(members)
+}
+
+fn glob_dirs(root: &Path, globs: &[String]) -> Result<Vec<PathBuf>> {
+    let mut dirs = Vec::new();
+    for pattern in globs {
+        let pattern = escaped_root(root).join(pattern);
+        let paths = glob::glob(&pattern.to_string_lossy())
+            .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+        for path in paths {
+            let path = path.map_err(Error::from)?;
+            if path.is_dir() && !dirs.contains(&path) {
+                dirs.push(path);
+            }
+        }
+    }
+    Ok(dirs)
+}
+
+/// Reads the name and the dependencies of the package in the given directory from its
+/// `Cargo.toml`, `package.json` or `pyproject.toml`.
+fn read_package(dir: &Path) -> Result<(Option<String>, Vec<String>)> {
+    let cargo_path = dir.join("Cargo.toml");
+    if cargo_path.exists() {
+        let manifest = parse_toml(&cargo_path, &read_file(&[], &cargo_path)?)?;
+        let name = manifest
+            .get("package")
+            .and_then(|p| p.get("name"))
+            .and_then(|n| n.as_str())
+            .map(String::from);
+        return Ok((name, cargo_dependencies(&manifest)));
+    }
+
+    let npm_path = dir.join("package.json");
+    if npm_path.exists() {
+        let package = parse_json(&npm_path)?;
+        let name = package["name"].as_str().map(String::from);
+        let dependencies = NPM_DEPENDENCY_OBJECTS
+            .iter()
+            .filter_map(|key| package.get(key)?.as_object())
+            .flat_map(|dependencies| dependencies.keys().cloned())
+            .collect();
+        return Ok((name, dependencies));
+    }
+
+    let python_path = dir.join("pyproject.toml");
+    if python_path.exists() {
+        let manifest = parse_toml(&python_path, &read_file(&[], &python_path)?)?;
+        let name = manifest
+            .get("project")
+            .and_then(|p| p.get("name"))
+            .or_else(|| manifest.get("tool")?.get("poetry")?.get("name"))
+            .and_then(|n| n.as_str())
+            .map(String::from);
+        return Ok((name, Vec::new()));
+    }
+
+    Ok((None, Vec::new()))
+}
+
+/// The names of the path dependencies of a Cargo manifest, including target-specific ones.
+fn cargo_dependencies(manifest: &Document) -> Vec<String> {
+    let targets = manifest
+        .get("target")
+        .and_then(|t| t.as_table_like())
+        .into_iter()
+        .flat_map(|targets| targets.iter().map(|(_, target)| target));
+    let tables = [manifest.as_item()].into_iter().chain(targets);
+
+    let mut names = Vec::new();
+    for table in tables {
+        for key in CARGO_DEPENDENCY_TABLES {
+            let Some(dependencies) = table.get(key).and_then(Item::as_table_like) else {
+                continue;
+            };
+            for (key, dependency) in dependencies.iter() {
+                let Some(dependency) = dependency.as_table_like() else {
+                    continue;
+                };
+                if dependency.contains_key("path") {
+                    let name = dependency.get("package").and_then(|p| p.as_str());
+                    names.push(name.unwrap_or(key).to_string());
+                }
+            }
+        }
+    }
+    names
+}
+
+fn parse_json(path: &Path) -> Result<serde_json::Value> {
+    serde_json::from_str(&read_file(&[], path)?).map_err(|e| {
+        let message = format!("failed to parse {}: {e}", path.display());
+        Error::new(ErrorKind::InvalidData, message)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MonorepoConfig;
+    use crate::manifests::tests::write_file;
+
+    #[test]
+    fn discover_cargo_packages() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write_file(
+            root,
+            "Cargo.toml",
+            "[workspace]\nmembers = [\"crates/*\"]\n",
+        );
+        write_file(
+            root,
+            "crates/core/Cargo.toml",
+            "[package]\nname = \"app-core\"\nversion = \"1.0.0\"\n",
+        );
+        write_file(
+            root,
+            "crates/cli/Cargo.toml",
+            r#"
+            [package]
+            name = "app-cli"
+            version = "1.0.0"
+
+            [dependencies]
+            core = { package = "app-core", path = "../core", version = "1.0.0" }
+            serde = "1"
+
+            [target.'cfg(unix)'.dev-dependencies]
+            app-core = { path = "../core" }
+            "#,
+        );
+        write_file(
+            root,
+            "crates/internal/Cargo.toml",
+            "[package]\nname = \"x\"\n",
+        );
+        for package in ["core", "cli"] {
+            write_file(root, &format!("crates/{package}/CHANGELOG.md"), "");
+        }
+
+        let mut packages = discover_packages(root, &Config::default()).unwrap();
+        packages.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(
+            packages,
+            vec![
+                Package {
+                    name: "app-cli".into(),
+                    dir: PathBuf::from("crates/cli"),
+                    dependencies: vec!["app-core".into()],
+                },
+                Package {
+                    name: "app-core".into(),
+                    dir: PathBuf::from("crates/core"),
+                    dependencies: vec![],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn discover_configured_packages() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write_file(root, "package.json", "{\"workspaces\": [\"js/*\"]}");
+        write_file(
+            root,
+            "js/web/package.json",
+            r#"{"name": "@app/web", "dependencies": {"@app/api": "^1.0.0"}}"#,
+        );
+        write_file(root, "js/web/CHANGELOG.md", "");
+        write_file(root, "js/api/package.json", r#"{"name": "@app/api"}"#);
+        write_file(root, "js/api/CHANGELOG.md", "");
+        write_file(
+            root,
+            "py/tool/pyproject.toml",
+            "[project]\nname = \"tool\"\n",
+        );
+        write_file(root, "py/tool/CHANGELOG.md", "");
+
+        let names = |config: &Config| {
+            let packages = discover_packages(root, config).unwrap();
+            let mut names = packages.into_iter().map(|p| p.name).collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+        assert_eq!(names(&Config::default()), vec!["@app/api", "@app/web"]);
+
+        let config = Config {
+            monorepo: Some(MonorepoConfig {
+                packages: vec!["js/web".into(), "py/*".into()],
+                ..MonorepoConfig::default()
+            }),
+            ..Config::default()
+        };
+        assert_eq!(names(&config), vec!["@app/web", "tool"]);
+    }
+
+    #[test]
+    fn release_dependants() {
+        let package = |name: &str, dependencies: &[&str]| Package {
+            name: name.into(),
+            dir: PathBuf::from(name),
+            dependencies: dependencies.iter().map(|d| d.to_string()).collect(),
+        };
+        let packages = vec![
+            package("a", &[]),
+            package("b", &["a"]),
+            package("c", &["b"]),
+            package("d", &[]),
+        ];
+
+        let released = with_dependants(&packages, &HashSet::from(["a".to_string()]));
+        let mut released = released.into_iter().collect::<Vec<_>>();
+        released.sort();
+        assert_eq!(released, vec!["a", "b", "c"]);
+    }
+}
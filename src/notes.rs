@@ -0,0 +1,102 @@
+use crate::generate::generate_release_body;
+use crate::json::JsonRelease;
+use crate::model::Release;
+use crate::scheme::Scheme;
+use clap::ValueEnum;
+
+#[derive(ValueEnum, Debug, Copy, Clone, Default)]
+pub enum NotesFormat {
+    #[default]
+    Markdown,
+    Text,
+    Json,
+}
+
+pub fn render_notes(release: &Release, format: NotesFormat, scheme: &Scheme) -> String {
+    match format {
+        NotesFormat::Markdown => generate_release_body(release),
+        NotesFormat::Text => render_text(release),
+        NotesFormat::Json => JsonRelease::new(release, scheme).to_string() + "\n",
+    }
+}
+
+fn render_text(release: &Release) -> String {
+    let mut str = String::new();
+    for section in release.sections.iter().filter(|s| !s.entries.is_empty()) {
+        if !str.is_empty() {
+            str.push('\n');
+        }
+        str.push_str(&format!("{}:\n", section.title));
+        for entry in &section.entries {
+            let text = entry.text.lines().collect::<Vec<_>>().join("\n    ");
+            str.push_str(&format!("  * {text}\n"));
+        }
+    }
+    str
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::ReleaseSelector;
+    use crate::parser::parse_str;
+
+    const CHANGELOG: &str = "# Changelog\n\n\
+        ## [Unreleased]\n\n\
+        ## [1.1.0] - 2023-03-15\n\n\
+        Some intro.\n\n\
+        ### Added\n\n* Add a\n* Add b\n  on two lines\n\n\
+        ### Fixed\n\n- Fix c\n\n\
+        ## [1.0.0] - 2023-03-01\n\n\
+        ### Added\n\n- Initial release\n\n\
+        ## [1.0.0-RC.1] - 2023-02-01\n\n";
+
+    #[test]
+    fn render_latest() {
+        let changelog = parse_str(CHANGELOG).unwrap();
+        let release = changelog.find(&ReleaseSelector::Latest).unwrap();
+
+        assert_eq!(
+            render_notes(release, NotesFormat::Markdown, &Scheme::SemVer),
+            "Some intro.\n\n### Added\n\n* Add a\n* Add b\n  on two lines\n\n### Fixed\n\n- Fix c\n"
+        );
+        assert_eq!(
+            render_notes(release, NotesFormat::Text, &Scheme::SemVer),
+            "Added:\n  * Add a\n  * Add b\n    on two lines\n\nFixed:\n  * Fix c\n"
+        );
+        assert_eq!(
+            render_notes(release, NotesFormat::Json, &Scheme::SemVer),
+            r#"{"version":"1.1.0","date":"2023-03-15","sections":[{"title":"Added","entries":["Add a","Add b\non two lines"]},{"title":"Fixed","entries":["Fix c"]}]}"#.to_string() + "\n"
+        );
+    }
+
+    #[test]
+    fn render_calver_json() {
+        let changelog = parse_str("# Changelog\n\n## [2026.09.1] - 2026-09-15\n\n").unwrap();
+        let release = changelog.find(&ReleaseSelector::Latest).unwrap();
+        let scheme = Scheme::CalVer("YYYY.0M.MICRO".parse().unwrap());
+
+        assert_eq!(
+            render_notes(release, NotesFormat::Json, &scheme),
+            r#"{"version":"2026.09.1","date":"2026-09-15","sections":[]}"#.to_string() + "\n"
+        );
+    }
+
+    #[test]
+    fn find_release() {
+        let changelog = parse_str(CHANGELOG).unwrap();
+        let find = |s: &str| {
+            changelog
+                .find(&s.parse().unwrap())
+                .map(|r| r.version.to_string())
+        };
+
+        assert_eq!(find("unreleased"), Some("Unreleased".into()));
+        assert_eq!(find("v1.0.0"), Some("1.0.0".into()));
+        assert_eq!(find("Latest"), Some("1.1.0".into()));
+        assert_eq!(find("V1.0.0-RC.1"), Some("1.0.0-RC.1".into()));
+        assert_eq!(find("1.0.0-rc.1"), None);
+        assert_eq!(find("2.0.0"), None);
+        assert!("foo".parse::<ReleaseSelector>().is_err());
+    }
+}
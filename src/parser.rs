@@ -1,7 +1,10 @@
-use crate::model::{Changelog, Ref, Release, Version};
+use crate::model::{parse_version, Changelog, Entry, Ref, Release, Section, Source, Version};
+use colored::{ColoredString, Colorize};
+use pest::error::LineColLocation;
 use pest::iterators::Pair;
-use pest::Parser;
-use semver::Version as SemVer;
+use pest::{Parser, Span};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
 use time::format_description::well_known::Iso8601;
 use time::Date;
 
@@ -9,10 +12,133 @@ use time::Date;
 #[grammar = "changelog.pest"]
 struct ChangelogParser;
 
-pub fn parse_str(content: &str) -> Changelog {
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub path: String,
+    pub line: usize,
+    pub column: usize,
+    pub length: usize,
+    pub snippet: String,
+    pub message: String,
+    pub hint: String,
+}
+
+impl ParseError {
+    fn from_span(span: Span, message: String, hint: impl Into<String>) -> Self {
+        let start = span.start_pos();
+        let (line, column) = start.line_col();
+        let snippet = start.line_of().trim_end().to_string();
+        let length = span
+            .as_str()
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .chars()
+            .count();
+        Self {
+            path: "CHANGELOG.md".into(),
+            line,
+            column,
+            length: length.max(1),
+            snippet,
+            message,
+            hint: hint.into(),
+        }
+    }
+
+    fn from_pest(error: pest::error::Error<Rule>, content: &str) -> Self {
+        let (line, column) = match error.line_col {
+            LineColLocation::Pos(pos) => pos,
+            LineColLocation::Span(start, _) => start,
+        };
+        // Pest marks line breaks with symbols, so take the line from the source instead
+        let snippet = content
+            .lines()
+            .nth(line - 1)
+            .unwrap_or_default()
+            .trim_end()
+            .to_string();
+        let hint = hint_for_line(&snippet);
+        Self {
+            path: "CHANGELOG.md".into(),
+            line,
+            column,
+            length: snippet.chars().count().saturating_sub(column - 1).max(1),
+            snippet,
+            message: "unexpected content in changelog".into(),
+            hint: hint.into(),
+        }
+    }
+
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        self.path = path.into();
+        self
+    }
+}
+
+fn hint_for_line(line: &str) -> &'static str {
+    if line.starts_with("## ") {
+        "release headings look like `## [1.2.3] - 2023-01-31` or `## [Unreleased]`"
+    } else if line.starts_with('#') {
+        "use `# Title` for the changelog title and `### Added` for sections of a release"
+    } else if line.starts_with(['-', '*', '+']) {
+        "list entries must be placed below a section heading like `### Added`"
+    } else if line.starts_with('[') {
+        "references like `[1.2.3]: https://...` must be placed at the end of the changelog"
+    } else {
+        "see https://keepachangelog.com for the expected format"
+    }
+}
+
+impl ParseError {
+    /// Renders the diagnostic, with colors if `color` is set.
+    pub fn render(&self, color: bool) -> String {
+        let paint = |text: &str, style: fn(&str) -> ColoredString| match color {
+            true => style(text).to_string(),
+            false => text.to_string(),
+        };
+        let line_number = self.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+        let bar = paint("|", |s| s.blue().bold());
+        let marker = " ".repeat(self.column - 1) + &"^".repeat(self.length);
+
+        format!(
+            "{}: {}\n\
+            {gutter}{} {}:{}:{}\n\
+            {gutter} {bar}\n\
+            {} {bar} {}\n\
+            {gutter} {bar} {}\n\
+            {gutter} {bar}\n\
+            {gutter} {} {}: {}",
+            paint("error", |s| s.red().bold()),
+            paint(&self.message, |s| s.bold()),
+            paint("-->", |s| s.blue().bold()),
+            self.path,
+            self.line,
+            self.column,
+            paint(&line_number, |s| s.blue().bold()),
+            self.snippet,
+            paint(&marker, |s| s.red().bold()),
+            paint("=", |s| s.blue().bold()),
+            paint("help", |s| s.bold()),
+            self.hint
+        )
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let color = colored::control::SHOULD_COLORIZE.should_colorize();
+        f.write_str(&self.render(color))
+    }
+}
+
+impl Error for ParseError {}
+
+pub fn parse_str(content: &str) -> Result<Changelog, ParseError> {
     let mut changelog = Changelog::new();
     let parsed = ChangelogParser::parse(Rule::Changelog, content)
-        .expect("failed to parse file")
+        .map_err(|error| ParseError::from_pest(error, content))?
         .next()
         .unwrap();
     for line in parsed.into_inner() {
@@ -21,14 +147,15 @@ pub fn parse_str(content: &str) -> Changelog {
                 changelog.intro = line.as_str().into();
             }
             Rule::Unreleased => {
-                let version = parse_release(line);
+                let version = parse_release(line)?;
                 changelog.releases.push(version);
             }
             Rule::Release => {
-                let version = parse_release(line);
+                let version = parse_release(line)?;
                 changelog.releases.push(version);
             }
             Rule::Reference => {
+                let text = line.as_str();
                 let mut inner_rules = line.into_inner();
 
                 let anchor = inner_rules
@@ -40,7 +167,8 @@ pub fn parse_str(content: &str) -> Changelog {
                     .as_str();
                 let href = inner_rules.next().unwrap().as_str();
 
-                let r = Ref::new(anchor.into(), href.into());
+                let mut r = Ref::new(anchor.into(), href.into());
+                r.source = Some(Source::new((r.anchor.clone(), r.href.clone()), text));
                 changelog.refs.push(r);
             }
             _ => {
@@ -48,10 +176,10 @@ pub fn parse_str(content: &str) -> Changelog {
             }
         }
     }
-    changelog
+    Ok(changelog)
 }
 
-fn parse_release(release_rules: Pair<Rule>) -> Release {
+fn parse_release(release_rules: Pair<Rule>) -> Result<Release, ParseError> {
     let mut v = Release::default();
     for line in release_rules.into_inner() {
         match line.as_rule() {
@@ -60,54 +188,67 @@ fn parse_release(release_rules: Pair<Rule>) -> Release {
             }
             Rule::UnreleasedHeading => {
                 v.version = Version::Unreleased;
+                v.heading = Some(Source::new((v.version.clone(), v.date), line.as_str()));
             }
             Rule::ReleaseHeading => {
+                let text = line.as_str();
                 let mut inner_rules = line.into_inner();
-                let version = inner_rules
-                    .next()
-                    .unwrap()
-                    .into_inner()
-                    .next()
-                    .unwrap()
-                    .as_str();
-                let date = inner_rules.next().unwrap().as_str();
+                let version = inner_rules.next().unwrap().into_inner().next().unwrap();
+                let date = inner_rules.next().unwrap();
 
-                v.version = Version::Released(SemVer::parse(version).unwrap());
-                v.date = Some(Date::parse(date, &Iso8601::DEFAULT).unwrap());
+                let semver = parse_version(version.as_str()).map_err(|e| {
+                    ParseError::from_span(
+                        version.as_span(),
+                        format!("invalid version `{}`: {e}", version.as_str()),
+                        "versions must follow semantic or calendar versioning, e.g. `1.2.3`, `1.2.3-next.0` or `2023.03.1`",
+                    )
+                })?;
+                let date = Date::parse(date.as_str(), &Iso8601::DEFAULT).map_err(|e| {
+                    ParseError::from_span(
+                        date.as_span(),
+                        format!("invalid date `{}`: {e}", date.as_str()),
+                        "dates must be valid ISO 8601 calendar dates like `2023-01-31`",
+                    )
+                })?;
+
+                v.version = Version::Released(semver);
+                v.date = Some(date);
+                v.heading = Some(Source::new((v.version.clone(), v.date), text));
             }
             Rule::Section => {
-                let mut inner_rules = line.into_inner();
-                let sec = inner_rules
-                    .next()
-                    .unwrap()
-                    .into_inner()
-                    .next()
-                    .unwrap()
-                    .as_str()
-                    .to_lowercase();
-                let bullets = inner_rules.next().unwrap();
+                v.sections.push(parse_section(line));
+            }
+            _ => {
+                // Do nothing
+            }
+        }
+    }
 
-                let bullets = bullets
-                    .into_inner()
-                    .map(|bullet| {
-                        bullet
-                            .into_inner()
-                            .map(|each| each.into_inner().next().unwrap().as_str())
-                            .fold(String::new(), |a, b| a + b + "\n")
-                            .trim()
-                            .to_string()
-                    })
-                    .collect::<Vec<_>>();
-
-                if sec == "added" {
-                    v.added = bullets;
-                } else if sec == "removed" {
-                    v.removed = bullets;
-                } else if sec == "fixed" {
-                    v.fixed = bullets;
-                } else if sec == "changed" {
-                    v.changed = bullets;
-                }
+    Ok(v)
+}
+
+fn parse_section(section_rules: Pair<Rule>) -> Section {
+    let mut section = Section::default();
+    for line in section_rules.into_inner() {
+        match line.as_rule() {
+            Rule::SectionHeading => {
+                let text = line.as_str();
+                section.title = line.into_inner().next().unwrap().as_str().into();
+                section.heading = Some(Source::new(section.title.clone(), text));
+            }
+            Rule::Bullet => {
+                let text = line.as_str();
+                let mut entry = Entry::new(
+                    line.into_inner()
+                        .map(|each| each.into_inner().next().unwrap().as_str())
+                        .fold(String::new(), |a, b| a + b + "\n")
+                        .trim(),
+                );
+                entry.source = Some(Source::new(entry.text.clone(), text));
+                section.entries.push(entry);
+            }
+            Rule::SectionTrailer => {
+                section.trailer = line.as_str().into();
             }
             _ => {
                 // Do nothing
@@ -115,5 +256,114 @@ fn parse_release(release_rules: Pair<Rule>) -> Release {
         }
     }
 
-    v
+    section
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::generate_str;
+    use semver::Version as SemVer;
+
+    fn entries<'a>(release: &'a Release, title: &str) -> Vec<&'a str> {
+        release
+            .sections
+            .iter()
+            .find(|s| s.title == title)
+            .map(|s| s.entries.iter().map(|e| e.text.as_str()).collect())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn parse_all_sections() {
+        let content = "# Changelog\n\n\
+            ## [1.0.0] - 2023-03-15\n\n\
+            ### Added\n\n- Add a\n\n\
+            ### Changed\n\n- Change b\n\n\
+            ### Deprecated\n\n- Deprecate c\n\n\
+            ### Removed\n\n- Remove d\n\n\
+            ### Fixed\n\n- Fix e\n\n\
+            ### Security\n\n- Secure f\n\n";
+        let changelog = parse_str(content).unwrap();
+        let release = &changelog.releases[0];
+        assert_eq!(entries(release, "Added"), vec!["Add a"]);
+        assert_eq!(entries(release, "Changed"), vec!["Change b"]);
+        assert_eq!(entries(release, "Deprecated"), vec!["Deprecate c"]);
+        assert_eq!(entries(release, "Removed"), vec!["Remove d"]);
+        assert_eq!(entries(release, "Fixed"), vec!["Fix e"]);
+        assert_eq!(entries(release, "Security"), vec!["Secure f"]);
+    }
+
+    #[test]
+    fn round_trip_is_lossless() {
+        let content = "# Changelog\n\n\
+            ## [Unreleased]\n\n\
+            ### Fixed\n\n* Fix a\n+ Fix b\n  over two lines\n\n<!-- keep me -->\n\n\
+            ### Internal\n\n- Refactor c\n\n\
+            ## [1.0.0] - 2023-03-15\n\n\
+            ### Removed\n\n- Remove d\n\n\
+            ### Added\n\n- Add e\n\n\
+            ## [0.9.0+build.5] - 2023-03-01\n\n\
+            ### Fixed\n\n- Fix f\n\n\
+            [unreleased]: https://example.com/compare/v1.0.0...HEAD\n\
+            [1.0.0]: https://example.com/releases/tag/v1.0.0\n\
+            [0.9.0+build.5]: https://example.com/releases/tag/v0.9.0+build.5\n";
+        assert_eq!(generate_str(&parse_str(content).unwrap()), content);
+    }
+
+    #[test]
+    fn round_trip_example() {
+        let content = include_str!("../example/CHANGELOG.md");
+        assert_eq!(generate_str(&parse_str(content).unwrap()), content);
+    }
+
+    #[test]
+    fn parse_calver_heading() {
+        let content = "# Changelog\n\n## [2023.03.1] - 2023-03-15\n\n### Fixed\n\n- Fix a\n";
+        let changelog = parse_str(content).unwrap();
+        let version = SemVer::parse("2023.3.1").unwrap();
+        assert_eq!(changelog.releases[0].version, Version::Released(version));
+        assert_eq!(generate_str(&changelog), content);
+    }
+
+    #[test]
+    fn report_invalid_version() {
+        let content = "# Changelog\n\n## [1.0] - 2023-03-15\n\n";
+        let error = parse_str(content).unwrap_err();
+        assert_eq!((error.line, error.column, error.length), (3, 5, 3));
+        assert_eq!(error.snippet, "## [1.0] - 2023-03-15");
+        assert!(error.message.starts_with("invalid version `1.0`"));
+    }
+
+    #[test]
+    fn report_invalid_date() {
+        let content = "# Changelog\n\n## [1.0.0] - 2023-13-15\n\n";
+        let error = parse_str(content).unwrap_err();
+        assert_eq!((error.line, error.column, error.length), (3, 14, 10));
+        assert!(error.message.starts_with("invalid date `2023-13-15`"));
+    }
+
+    #[test]
+    fn report_stray_line() {
+        let content = "# Changelog\n\n## [1.0.0] - 2023-03-15\n\n### Added\n\n- Add a\n\n## Oops\n";
+        let error = parse_str(content)
+            .unwrap_err()
+            .with_path("docs/CHANGELOG.md");
+        assert_eq!((error.line, error.column), (9, 4));
+        assert_eq!(error.snippet, "## Oops");
+        assert!(error.hint.contains("release headings"));
+
+        let rendered = error.render(false);
+        assert!(rendered.contains(" --> docs/CHANGELOG.md:9:4\n"));
+        assert!(rendered.contains("9 | ## Oops\n  |    ^^^^\n"));
+    }
+
+    #[test]
+    fn report_line_without_markers() {
+        let content = "# Changelog\n\n## [1.0.0] - 2023-03-15\n\n### Added\n\n- a\n\n## \n";
+        let error = parse_str(content).unwrap_err();
+        assert_eq!((error.line, error.column), (9, 4));
+        assert_eq!(error.snippet, "##");
+        assert!(error.render(false).contains("9 | ##\n"));
+    }
 }
@@ -0,0 +1,213 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+
+/// A git hosting service, used to build the compare URLs of the changelog references.
+#[derive(ValueEnum, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Host {
+    Github,
+    Gitlab,
+    Bitbucket,
+    /// Gitea and Forgejo, like Codeberg.
+    Gitea,
+    Azure,
+}
+
+/// The URL templates of the references, using `{previous}` and `{current}` tags.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompareUrls {
+    /// Compares two releases.
+    pub release: String,
+    /// Compares the latest release with the unreleased changes, where `{current}` is `HEAD`.
+    pub unreleased: String,
+    /// Links the first release, which has no previous tag to compare with.
+    pub tag: Option<String>,
+}
+
+impl Host {
+    /// Guesses the service from the host name of a remote.
+    fn detect(hostname: &str) -> Option<Self> {
+        let hostname = hostname.to_lowercase();
+        if hostname.contains("github") {
+            Some(Host::Github)
+        } else if hostname.contains("gitlab") {
+            Some(Host::Gitlab)
+        } else if hostname == "bitbucket.org" {
+            Some(Host::Bitbucket)
+        } else if hostname.ends_with("dev.azure.com") || hostname.ends_with(".visualstudio.com") {
+            Some(Host::Azure)
+        } else if ["gitea", "forgejo", "codeberg.org"]
+            .iter()
+            .any(|name| hostname.contains(name))
+        {
+            Some(Host::Gitea)
+        } else {
+            None
+        }
+    }
+
+    /// The compare URLs of a repository with the given web URL.
+    ///
+    /// Azure DevOps cannot compare against `HEAD`, so unreleased changes are compared with
+    /// the given default branch instead.
+    pub fn compare_urls(&self, repository: &str, default_branch: &str) -> CompareUrls {
+        let release = match self {
+            Host::Github | Host::Gitea => {
+                format!("{repository}/compare/{{previous}}...{{current}}")
+            }
+            Host::Gitlab => format!("{repository}/-/compare/{{previous}}...{{current}}"),
+            Host::Bitbucket => format!("{repository}/branches/compare/{{current}}%0D{{previous}}"),
+            Host::Azure => format!(
+                "{repository}/branchCompare?baseVersion=GT{{previous}}&targetVersion=GT{{current}}"
+            ),
+        };
+        let unreleased = match self {
+            Host::Azure => format!(
+                "{repository}/branchCompare?baseVersion=GT{{previous}}&targetVersion=GB{default_branch}"
+            ),
+            _ => release.clone(),
+        };
+        let tag = match self {
+            Host::Github | Host::Gitea => format!("{repository}/releases/tag/{{current}}"),
+            Host::Gitlab => format!("{repository}/-/tags/{{current}}"),
+            Host::Bitbucket => format!("{repository}/src/{{current}}"),
+            Host::Azure => format!("{repository}?version=GT{{current}}"),
+        };
+        CompareUrls {
+            release,
+            unreleased,
+            tag: Some(tag),
+        }
+    }
+}
+
+/// Splits a git remote URL into its host name and the web URL of the repository.
+///
+/// Supports HTTPS and SSH remotes, including the scp-like `git@host:owner/repo.git` syntax
+/// and the SSH remotes of Azure DevOps.
+pub fn parse_remote(remote: &str) -> Option<(String, String)> {
+    let remote = remote.trim();
+    let (hostname, path) = match remote.split_once("://") {
+        Some((_, rest)) => {
+            let (authority, path) = rest.split_once('/')?;
+            let hostname = authority.rsplit('@').next()?.split(':').next()?;
+            (hostname, path)
+        }
+        None => {
+            let (authority, path) = remote.split_once(':')?;
+            (authority.rsplit('@').next()?, path)
+        }
+    };
+    let path = path.trim_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    if hostname.is_empty() || path.is_empty() {
+        return None;
+    }
+
+    if hostname == "ssh.dev.azure.com" || hostname.starts_with("vs-ssh.") {
+        let path = path.strip_prefix("v3/").unwrap_or(path);
+        let mut parts = path.splitn(3, '/');
+        let (organization, project, repository) = (parts.next()?, parts.next()?, parts.next()?);
+        let url = format!("https://dev.azure.com/{organization}/{project}/_git/{repository}");
+        return Some(("dev.azure.com".into(), url));
+    }
+
+    Some((hostname.to_string(), format!("https://{hostname}/{path}")))
+}
+
+/// Infers the compare URLs from a git remote URL, unless the host is unknown.
+pub fn infer_compare_urls(
+    remote: &str,
+    host: Option<Host>,
+    default_branch: &str,
+) -> Option<CompareUrls> {
+    let (hostname, repository) = parse_remote(remote)?;
+    let host = host.or_else(|| Host::detect(&hostname))?;
+    Some(host.compare_urls(&repository, default_branch))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_remotes() {
+        let repository = |remote: &str| parse_remote(remote).map(|(_, url)| url);
+
+        let github = Some("https://github.com/ksm2/protokollant".to_string());
+        assert_eq!(repository("git@github.com:ksm2/protokollant.git"), github);
+        assert_eq!(
+            repository("https://github.com/ksm2/protokollant.git"),
+            github
+        );
+        assert_eq!(
+            repository("https://token@github.com/ksm2/protokollant"),
+            github
+        );
+        assert_eq!(
+            repository("ssh://git@github.com:22/ksm2/protokollant.git"),
+            github
+        );
+        assert_eq!(
+            repository("git@gitlab.example.com:group/sub/project.git"),
+            Some("https://gitlab.example.com/group/sub/project".into())
+        );
+
+        let azure = Some("https://dev.azure.com/org/project/_git/repo".to_string());
+        assert_eq!(
+            repository("git@ssh.dev.azure.com:v3/org/project/repo"),
+            azure
+        );
+        assert_eq!(
+            repository("https://org@dev.azure.com/org/project/_git/repo"),
+            azure
+        );
+
+        assert_eq!(repository("/srv/git/repo.git"), None);
+    }
+
+    #[test]
+    fn infer_urls() {
+        let release = |remote: &str, host: Option<Host>| {
+            infer_compare_urls(remote, host, "main").map(|urls| urls.release)
+        };
+
+        assert_eq!(
+            release("git@github.com:a/b.git", None).as_deref(),
+            Some("https://github.com/a/b/compare/{previous}...{current}")
+        );
+        assert_eq!(
+            release("https://gitlab.com/a/b.git", None).as_deref(),
+            Some("https://gitlab.com/a/b/-/compare/{previous}...{current}")
+        );
+        assert_eq!(
+            release("git@bitbucket.org:a/b.git", None).as_deref(),
+            Some("https://bitbucket.org/a/b/branches/compare/{current}%0D{previous}")
+        );
+        assert_eq!(
+            release("https://codeberg.org/a/b.git", None).as_deref(),
+            Some("https://codeberg.org/a/b/compare/{previous}...{current}")
+        );
+        assert_eq!(release("git@git.example.com:a/b.git", None), None);
+        assert_eq!(
+            release("git@git.example.com:a/b.git", Some(Host::Gitea)).as_deref(),
+            Some("https://git.example.com/a/b/compare/{previous}...{current}")
+        );
+
+        let azure = infer_compare_urls("git@ssh.dev.azure.com:v3/o/p/r", None, "develop").unwrap();
+        assert_eq!(
+            azure.unreleased,
+            "https://dev.azure.com/o/p/_git/r/branchCompare?baseVersion=GT{previous}&targetVersion=GBdevelop"
+        );
+        assert_eq!(
+            azure.tag.as_deref(),
+            Some("https://dev.azure.com/o/p/_git/r?version=GT{current}")
+        );
+
+        let gitlab = infer_compare_urls("https://gitlab.com/a/b.git", None, "main").unwrap();
+        assert_eq!(
+            gitlab.tag.as_deref(),
+            Some("https://gitlab.com/a/b/-/tags/{current}")
+        );
+    }
+}
@@ -0,0 +1,276 @@
+use crate::model::{Bump, Change};
+use clap::ValueEnum;
+use semver::{Prerelease, Version as SemVer};
+use serde::Deserialize;
+use std::str::FromStr;
+use time::Date;
+
+#[derive(ValueEnum, Deserialize, Debug, Copy, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SchemeKind {
+    #[default]
+    Semver,
+    Calver,
+}
+
+/// How versions are computed and written.
+///
+/// Versions of all schemes are represented by semver versions internally, so that they can be
+/// compared and written to manifests. CalVer versions map their components onto the major,
+/// minor and patch numbers and only differ in the way they are bumped and formatted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Scheme {
+    SemVer,
+    CalVer(CalVerFormat),
+}
+
+impl Scheme {
+    /// Computes the version following `current`, released on the given date.
+    pub fn bump(
+        &self,
+        current: &SemVer,
+        change: Change,
+        preid: Option<&str>,
+        today: Date,
+    ) -> Result<SemVer, String> {
+        match self {
+            Scheme::SemVer => Ok(current.bump(change, preid)),
+            Scheme::CalVer(format) => format.bump(current, change, preid, today),
+        }
+    }
+
+    /// Formats a version the way it appears in the changelog and in tags.
+    pub fn format(&self, version: &SemVer) -> String {
+        match self {
+            Scheme::SemVer => version.to_string(),
+            Scheme::CalVer(format) => format.format(version),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum CalVerToken {
+    /// Full year, like `2023`.
+    FullYear,
+    /// Year since 2000, like `23`, zero-padded with `0Y`.
+    ShortYear {
+        padded: bool,
+    },
+    Month {
+        padded: bool,
+    },
+    /// ISO week of the year.
+    Week {
+        padded: bool,
+    },
+    Day {
+        padded: bool,
+    },
+    /// Counter of releases within the same period.
+    Micro,
+}
+
+impl CalVerToken {
+    /// The value of this token on `date`, or `None` for the release counter.
+    fn value(&self, date: Date) -> Result<Option<u64>, String> {
+        let year = |since: i32| {
+            u64::try_from(date.year() - since)
+                .map_err(|_| format!("The year of {date} cannot be represented in the version"))
+        };
+        Ok(match self {
+            CalVerToken::FullYear => Some(year(0)?),
+            CalVerToken::ShortYear { .. } => Some(year(2000)?),
+            CalVerToken::Month { .. } => Some(u8::from(date.month()) as u64),
+            CalVerToken::Week { .. } => Some(date.iso_week() as u64),
+            CalVerToken::Day { .. } => Some(date.day() as u64),
+            CalVerToken::Micro => None,
+        })
+    }
+
+    fn padded(&self) -> bool {
+        match self {
+            CalVerToken::ShortYear { padded }
+            | CalVerToken::Month { padded }
+            | CalVerToken::Week { padded }
+            | CalVerToken::Day { padded } => *padded,
+            _ => false,
+        }
+    }
+}
+
+/// A CalVer format like `YYYY.0M.MICRO` with three dot-separated components.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct CalVerFormat {
+    tokens: [CalVerToken; 3],
+}
+
+impl Default for CalVerFormat {
+    fn default() -> Self {
+        "YYYY.0M.MICRO".parse().unwrap()
+    }
+}
+
+impl CalVerFormat {
+    fn bump(
+        &self,
+        current: &SemVer,
+        change: Change,
+        preid: Option<&str>,
+        today: Date,
+    ) -> Result<SemVer, String> {
+        let components = [current.major, current.minor, current.patch];
+        let values = self
+            .tokens
+            .iter()
+            .map(|token| token.value(today))
+            .collect::<Result<Vec<_>, _>>()?;
+        let same_period = values
+            .iter()
+            .zip(components)
+            .all(|(value, component)| value.is_none_or(|v| v == component));
+
+        if same_period && !current.pre.is_empty() {
+            return Ok(match change {
+                Change::Prerelease => current.bump(change, preid),
+                _ => SemVer::new(current.major, current.minor, current.patch),
+            });
+        }
+
+        let mut next = [0; 3];
+        for (index, value) in values.into_iter().enumerate() {
+            next[index] = match value {
+                Some(value) => value,
+                None if !same_period => 0,
+                None => components[index] + 1,
+            };
+        }
+        let mut next = SemVer::new(next[0], next[1], next[2]);
+        if next <= *current {
+            return Err(format!(
+                "Version {} has already been released, add MICRO to the format to release more than once per period",
+                self.format(current)
+            ));
+        }
+        if let Change::Prerelease = change {
+            next.pre = Prerelease::new(&format!("{}.0", preid.unwrap_or("next")))
+                .expect("valid prerelease identifier");
+        }
+        Ok(next)
+    }
+
+    fn format(&self, version: &SemVer) -> String {
+        let components = [version.major, version.minor, version.patch];
+        let mut str = self
+            .tokens
+            .iter()
+            .zip(components)
+            .map(|(token, component)| match token.padded() {
+                true => format!("{component:02}"),
+                false => component.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(".");
+        if !version.pre.is_empty() {
+            str.push_str(&format!("-{}", version.pre));
+        }
+        if !version.build.is_empty() {
+            str.push_str(&format!("+{}", version.build));
+        }
+        str
+    }
+}
+
+impl FromStr for CalVerFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = s
+            .split('.')
+            .map(|token| match token {
+                "YYYY" => Ok(CalVerToken::FullYear),
+                "YY" => Ok(CalVerToken::ShortYear { padded: false }),
+                "0Y" => Ok(CalVerToken::ShortYear { padded: true }),
+                "MM" => Ok(CalVerToken::Month { padded: false }),
+                "0M" => Ok(CalVerToken::Month { padded: true }),
+                "WW" => Ok(CalVerToken::Week { padded: false }),
+                "0W" => Ok(CalVerToken::Week { padded: true }),
+                "DD" => Ok(CalVerToken::Day { padded: false }),
+                "0D" => Ok(CalVerToken::Day { padded: true }),
+                "MICRO" => Ok(CalVerToken::Micro),
+                _ => Err(format!("unknown CalVer token `{token}` in `{s}`")),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let tokens: [CalVerToken; 3] = tokens
+            .try_into()
+            .map_err(|_| format!("CalVer format `{s}` must have three components"))?;
+        if tokens[..2].contains(&CalVerToken::Micro) {
+            return Err(format!("MICRO must be the last component of `{s}`"));
+        }
+        Ok(Self { tokens })
+    }
+}
+
+impl TryFrom<String> for CalVerFormat {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::Month;
+
+    #[test]
+    fn bump_calver() {
+        let scheme = Scheme::CalVer("YYYY.0M.MICRO".parse().unwrap());
+        let today = Date::from_calendar_date(2023, Month::March, 15).unwrap();
+        let bump = |v: &str, change: Change, preid: Option<&str>| {
+            let current = SemVer::parse(v).unwrap();
+            let next = scheme.bump(&current, change, preid, today).unwrap();
+            scheme.format(&next)
+        };
+
+        assert_eq!(bump("2023.2.4", Change::Patch, None), "2023.03.0");
+        assert_eq!(bump("2023.3.0", Change::Major, None), "2023.03.1");
+        assert_eq!(
+            bump("2023.3.1", Change::Prerelease, Some("rc")),
+            "2023.03.2-rc.0"
+        );
+        assert_eq!(
+            bump("2023.3.2-rc.0", Change::Prerelease, None),
+            "2023.03.2-rc.1"
+        );
+        assert_eq!(bump("2023.3.2-rc.1", Change::Minor, None), "2023.03.2");
+        assert_eq!(bump("2022.12.3-rc.1", Change::Minor, None), "2023.03.0");
+    }
+
+    #[test]
+    fn bump_calver_without_micro() {
+        let scheme = Scheme::CalVer("0Y.0M.0D".parse().unwrap());
+        let today = Date::from_calendar_date(2023, Month::March, 5).unwrap();
+        let current = SemVer::parse("23.3.1").unwrap();
+        let next = scheme.bump(&current, Change::Patch, None, today).unwrap();
+        assert_eq!(scheme.format(&next), "23.03.05");
+        assert!(scheme.bump(&next, Change::Patch, None, today).is_err());
+
+        let before_2000 = Date::from_calendar_date(1999, Month::December, 31).unwrap();
+        assert_eq!(
+            scheme.bump(&current, Change::Patch, None, before_2000),
+            Err("The year of 1999-12-31 cannot be represented in the version".into())
+        );
+    }
+
+    #[test]
+    fn parse_calver_format() {
+        assert!("YYYY.0M.MICRO".parse::<CalVerFormat>().is_ok());
+        assert!("YYYY.WW.DD".parse::<CalVerFormat>().is_ok());
+        assert!("YYYY.MICRO.0M".parse::<CalVerFormat>().is_err());
+        assert!("YYYY.0M".parse::<CalVerFormat>().is_err());
+        assert!("YYYY.0M.QQ".parse::<CalVerFormat>().is_err());
+    }
+}
@@ -0,0 +1,140 @@
+use crate::diff::FileDiff;
+use std::fs::{metadata, read_to_string, remove_file, rename, set_permissions, write};
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+/// Writes all changed files, or none of them.
+///
+/// Every file is checked to still have the content it was diffed against before anything is
+/// written. Files are then replaced atomically one by one, and if one of them fails, all files
+/// written so far are restored to their previous content.
+pub fn write_files(diffs: &[FileDiff]) -> Result<()> {
+    let changed = diffs.iter().filter(|d| d.is_changed()).collect::<Vec<_>>();
+    for diff in &changed {
+        validate(diff)?;
+    }
+    apply_all(&changed)
+}
+
+fn validate(diff: &FileDiff) -> Result<()> {
+    let current = read_to_string(diff.path()).map_err(|e| {
+        let message = format!("cannot read {}: {e}", diff.filename());
+        Error::new(e.kind(), message)
+    })?;
+    if current != diff.left() {
+        let message = format!("{} was changed while releasing", diff.filename());
+        return Err(Error::new(ErrorKind::InvalidData, message));
+    }
+    Ok(())
+}
+
+fn apply_all(diffs: &[&FileDiff]) -> Result<()> {
+    for (index, diff) in diffs.iter().enumerate() {
+        if let Err(error) = apply(diff) {
+            for written in diffs[..index].iter().rev() {
+                if let Err(rollback_error) = write_atomic(written.path(), written.left()) {
+                    eprintln!("Failed to restore {}: {rollback_error}", written.filename());
+                }
+            }
+            let message = format!("failed to write {}: {error}", diff.filename());
+            return Err(Error::new(error.kind(), message));
+        }
+    }
+    Ok(())
+}
+
+fn apply(diff: &FileDiff) -> Result<()> {
+    if diff.is_deleted() {
+        remove_file(diff.path())
+    } else {
+        write_atomic(diff.path(), diff.right())
+    }
+}
+
+/// Replaces a file by writing a temporary file next to it and renaming it over the original.
+fn write_atomic(path: &Path, content: &str) -> Result<()> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "path has no file name"))?;
+    let temp = path.with_file_name(format!(
+        ".{}.{}.tmp",
+        name.to_string_lossy(),
+        std::process::id()
+    ));
+
+    let result = write(&temp, content).and_then(|_| {
+        if let Ok(metadata) = metadata(path) {
+            set_permissions(&temp, metadata.permissions())?;
+        }
+        rename(&temp, path)
+    });
+    if result.is_err() {
+        let _ = remove_file(&temp);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::create_dir;
+
+    #[test]
+    fn write_all_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write(root.join("a.txt"), "a\n").unwrap();
+        write(root.join("b.txt"), "b\n").unwrap();
+        write(root.join("c.txt"), "c\n").unwrap();
+
+        let diffs = vec![
+            FileDiff::new(root, root.join("a.txt"), "a\n".into(), "A\n".into()),
+            FileDiff::new(root, root.join("b.txt"), "b\n".into(), "b\n".into()),
+            FileDiff::deletion(root, root.join("c.txt"), "c\n".into()),
+        ];
+        write_files(&diffs).unwrap();
+
+        assert_eq!(read_to_string(root.join("a.txt")).unwrap(), "A\n");
+        assert_eq!(read_to_string(root.join("b.txt")).unwrap(), "b\n");
+        assert!(!root.join("c.txt").exists());
+        assert_eq!(std::fs::read_dir(root).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn refuse_files_changed_on_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write(root.join("a.txt"), "a\n").unwrap();
+        write(root.join("b.txt"), "changed\n").unwrap();
+
+        let diffs = vec![
+            FileDiff::new(root, root.join("a.txt"), "a\n".into(), "A\n".into()),
+            FileDiff::new(root, root.join("b.txt"), "b\n".into(), "B\n".into()),
+        ];
+        let error = write_files(&diffs).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert_eq!(read_to_string(root.join("a.txt")).unwrap(), "a\n");
+    }
+
+    #[test]
+    fn roll_back_on_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write(root.join("a.txt"), "a\n").unwrap();
+        write(root.join("b.txt"), "b\n").unwrap();
+        create_dir(root.join("c.txt")).unwrap();
+        write(root.join("c.txt/keep"), "").unwrap();
+
+        let diffs = [
+            FileDiff::new(root, root.join("a.txt"), "a\n".into(), "A\n".into()),
+            FileDiff::deletion(root, root.join("b.txt"), "b\n".into()),
+            FileDiff::new(root, root.join("c.txt"), "c\n".into(), "C\n".into()),
+        ];
+        assert!(apply_all(&diffs.iter().collect::<Vec<_>>()).is_err());
+
+        assert_eq!(read_to_string(root.join("a.txt")).unwrap(), "a\n");
+        assert_eq!(read_to_string(root.join("b.txt")).unwrap(), "b\n");
+        assert!(root.join("c.txt/keep").exists());
+        assert_eq!(std::fs::read_dir(root).unwrap().count(), 3);
+    }
+}